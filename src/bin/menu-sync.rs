@@ -0,0 +1,121 @@
+//! Syncs the custom menus of an official account with a TOML or JSON
+//! definition.
+//!
+//! ```text
+//! menu-sync [--dry-run] <menu.toml|menu.json>
+//! ```
+//!
+//! The account is read from the `APPID` and `APP_SECRET` environment
+//! variables. Set `REDIS_URL` to share the access token with the servers of
+//! the account, which needs the `redis` feature; otherwise a new token is
+//! fetched.
+
+use std::{env, fs, path::Path, process::ExitCode, sync::Arc};
+
+use async_wechat::{
+    Config, OfficialAccount, Result, WechatError, cache::Cache, cache::MemoryCache,
+    official_account::menu_sync::MenuDefinition,
+};
+
+const USAGE: &str = "usage: menu-sync [--dry-run] <menu.toml|menu.json>";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut dry_run = false;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let extension = Path::new(&path).extension().and_then(|ext| ext.to_str());
+    let parse = match extension {
+        Some("toml") => MenuDefinition::from_toml,
+        Some("json") => MenuDefinition::from_json,
+        _ => {
+            eprintln!("{}: expected a .toml or .json file", path);
+            return ExitCode::from(2);
+        }
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("cannot read {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(parse(&contents), dry_run).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(definition: Result<MenuDefinition>, dry_run: bool) -> Result<()> {
+    let definition = definition?;
+    let account = account()?;
+
+    let diff = if dry_run {
+        account.diff_menu(&definition).await?
+    } else {
+        account.sync_menu(&definition).await?
+    };
+
+    if !diff.has_changes() {
+        println!("menu is up to date");
+        return Ok(());
+    }
+
+    print!("{}", diff);
+    if !dry_run {
+        println!("menu updated");
+    }
+
+    Ok(())
+}
+
+/// Builds an API-only account. The token and AES key only serve callbacks,
+/// which a menu push never receives, so they are left empty.
+fn account() -> Result<OfficialAccount> {
+    let var = |name: &'static str| {
+        env::var(name)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| WechatError::Config(format!("{} is not set", name)))
+    };
+    let config = Config {
+        appid: var("APPID")?,
+        app_secret: var("APP_SECRET")?,
+        token: String::new(),
+        encoding_aes_key: None,
+    };
+
+    let cache: Arc<dyn Cache> = match env::var("REDIS_URL") {
+        #[cfg(feature = "redis")]
+        Ok(redis_url) => Arc::new(async_wechat::cache::RedisCache::from_url(redis_url)?),
+        // A fresh token would invalidate the one the servers share.
+        #[cfg(not(feature = "redis"))]
+        Ok(_) => {
+            return Err(WechatError::Config(
+                "REDIS_URL is set but menu-sync was built without the redis feature".to_string(),
+            ));
+        }
+        Err(_) => Arc::new(MemoryCache::new()),
+    };
+
+    Ok(OfficialAccount::with_cache(config, cache))
+}
@@ -0,0 +1,356 @@
+use reqwest::{Client, Proxy};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::cache::Cache;
+use crate::constants::{keys, urls};
+use crate::{Config, OfficialAccount, Result, TokenPolicy, WechatError};
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::cache::MemoryCache;
+    use crate::official_account::qrcode::TicketResponse;
+    use crate::{Config, OfficialAccount, WechatError};
+
+    fn config() -> Config {
+        Config {
+            appid: "wx1234567890abcdef".to_string(),
+            app_secret: "secret".to_string(),
+            token: "wechat".to_string(),
+            encoding_aes_key: Some("abcdefghijklmnopqrstuvwxyz0123456789ABCDEFG".to_string()),
+        }
+    }
+
+    #[test]
+    fn build() {
+        let account = OfficialAccount::builder(config())
+            .cache(Arc::new(MemoryCache::new()))
+            .timeout(Duration::from_secs(5))
+            .user_agent("async-wechat-test")
+            .api_base_url("http://127.0.0.1:8080/")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            account.api_url("/cgi-bin/token"),
+            "http://127.0.0.1:8080/cgi-bin/token"
+        );
+    }
+
+    #[test]
+    fn build_with_open_and_mp_base_urls() {
+        let account = OfficialAccount::builder(config())
+            .cache(Arc::new(MemoryCache::new()))
+            .open_base_url("http://127.0.0.1:8081")
+            .mp_base_url("http://127.0.0.1:8082")
+            .build()
+            .unwrap();
+
+        let url = account.get_redirect_url(
+            "https://example.com/callback".to_string(),
+            "snsapi_base".to_string(),
+            None,
+        );
+        assert_eq!(
+            url,
+            "http://127.0.0.1:8081/connect/oauth2/authorize?appid=wx1234567890abcdef&redirect_uri=https%3A%2F%2Fexample.com%2Fcallback&response_type=code&scope=snsapi_base&state=#wechat_redirect"
+        );
+
+        let ticket = TicketResponse {
+            ticket: "gQH47joAAAAAAAAAASxodHRwOi8v".to_string(),
+            expire_seconds: 60,
+            url: "http://weixin.qq.com/q/kZgfwMTm72WWPkovabbI".to_string(),
+        };
+        assert_eq!(
+            account.show_qrcode(&ticket),
+            "http://127.0.0.1:8082/cgi-bin/showqrcode?ticket=gQH47joAAAAAAAAAASxodHRwOi8v"
+        );
+    }
+
+    #[test]
+    fn build_without_cache() {
+        let err = OfficialAccount::builder(config()).build().err().unwrap();
+        assert!(matches!(err, WechatError::Config(_)));
+    }
+
+    #[test]
+    fn build_with_client_and_http_settings() {
+        let err = OfficialAccount::builder(config())
+            .cache(Arc::new(MemoryCache::new()))
+            .http_client(reqwest::Client::new())
+            .timeout(Duration::from_secs(5))
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, WechatError::Config(_)));
+    }
+
+    #[test]
+    fn build_with_invalid_base_url() {
+        let err = OfficialAccount::builder(config())
+            .cache(Arc::new(MemoryCache::new()))
+            .api_base_url("api.weixin.qq.com")
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, WechatError::Config(_)));
+    }
+
+    #[test]
+    fn validate_config() {
+        assert!(config().validate().is_ok());
+
+        let mut conf = config();
+        conf.appid = "gh_1234567890ab".to_string();
+        assert!(conf.validate().is_err());
+
+        let mut conf = config();
+        conf.encoding_aes_key = Some("too-short".to_string());
+        assert!(conf.validate().is_err());
+
+        let mut conf = config();
+        conf.token = "".to_string();
+        assert!(conf.validate().is_err());
+    }
+}
+
+/// Builds an [`OfficialAccount`], validating its configuration.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use async_wechat::{Config, OfficialAccount};
+///
+/// # #[cfg(feature = "redis")]
+/// # fn main() -> async_wechat::Result<()> {
+/// let config = Config {
+///     appid: "wx1234567890abcdef".to_string(),
+///     app_secret: "secret".to_string(),
+///     token: "wechat".to_string(),
+///     encoding_aes_key: None,
+/// };
+///
+/// let account = OfficialAccount::builder(config)
+///     .redis_url("redis://127.0.0.1/")
+///     .timeout(Duration::from_secs(10))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "redis"))]
+/// # fn main() {}
+/// ```
+pub struct OfficialAccountBuilder {
+    config: Config,
+    cache: Option<Arc<dyn Cache>>,
+    #[cfg(feature = "redis")]
+    redis_url: Option<String>,
+    client: Option<Client>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxy: Option<String>,
+    user_agent: Option<String>,
+    api_base_url: String,
+    open_base_url: String,
+    mp_base_url: String,
+    token_policy: TokenPolicy,
+    namespace: String,
+}
+
+impl OfficialAccountBuilder {
+    pub(crate) fn new(config: Config) -> Self {
+        OfficialAccountBuilder {
+            config,
+            cache: None,
+            #[cfg(feature = "redis")]
+            redis_url: None,
+            client: None,
+            timeout: None,
+            connect_timeout: None,
+            proxy: None,
+            user_agent: None,
+            api_base_url: urls::API_BASE_URL.to_string(),
+            open_base_url: urls::OPEN_BASE_URL.to_string(),
+            mp_base_url: urls::MP_BASE_URL.to_string(),
+            token_policy: TokenPolicy::default(),
+            namespace: keys::DEFAULT_NAMESPACE.to_string(),
+        }
+    }
+
+    /// Stores tokens in the given cache backend.
+    pub fn cache(mut self, cache: Arc<dyn Cache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Stores tokens in an existing Redis pool.
+    #[cfg(feature = "redis")]
+    pub fn redis_pool(self, pool: deadpool_redis::Pool) -> Self {
+        self.cache(Arc::new(crate::cache::RedisCache::new(pool)))
+    }
+
+    /// Stores tokens in Redis, creating a pool for the given connection string.
+    #[cfg(feature = "redis")]
+    pub fn redis_url(mut self, redis_url: impl Into<String>) -> Self {
+        self.redis_url = Some(redis_url.into());
+        self
+    }
+
+    /// Uses an existing HTTP client.
+    ///
+    /// Cannot be combined with `timeout`, `connect_timeout`, `proxy` or
+    /// `user_agent`, which configure the client built by the SDK.
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets the total timeout of each HTTP request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for establishing HTTP connections.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Routes all HTTP requests through the given proxy URL.
+    pub fn proxy(mut self, proxy_url: impl Into<String>) -> Self {
+        self.proxy = Some(proxy_url.into());
+        self
+    }
+
+    /// Sets the `User-Agent` header of HTTP requests.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Sends API requests to `base_url` instead of `https://api.weixin.qq.com`,
+    /// e.g. an egress proxy or a local mock server.
+    pub fn api_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.api_base_url = base_url.into();
+        self
+    }
+
+    /// Builds OAuth2 authorization URLs on `base_url` instead of
+    /// `https://open.weixin.qq.com`.
+    pub fn open_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.open_base_url = base_url.into();
+        self
+    }
+
+    /// Builds QR code image URLs on `base_url` instead of
+    /// `https://mp.weixin.qq.com`.
+    pub fn mp_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.mp_base_url = base_url.into();
+        self
+    }
+
+    /// Sets how long access tokens are cached and when they are refreshed.
+    pub fn token_policy(mut self, policy: TokenPolicy) -> Self {
+        self.token_policy = policy;
+        self
+    }
+
+    /// Sets the prefix of every cache key (`async-wechat` by default).
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Validates the configuration and builds the account.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::Config` if the `Config` is invalid, if no cache
+    ///   backend was given, if a base URL cannot be parsed, or if an HTTP client
+    ///   was given together with HTTP settings.
+    /// * Returns `WechatError::Http` if the HTTP client cannot be built.
+    pub fn build(self) -> Result<OfficialAccount> {
+        self.config.validate()?;
+
+        #[cfg(feature = "redis")]
+        let cache = match (self.cache, self.redis_url) {
+            (Some(_), Some(_)) => {
+                return Err(WechatError::Config(
+                    "a cache and a Redis URL cannot both be set".to_string(),
+                ));
+            }
+            (Some(cache), None) => Some(cache),
+            (None, Some(redis_url)) => {
+                Some(Arc::new(crate::cache::RedisCache::from_url(redis_url)?) as Arc<dyn Cache>)
+            }
+            (None, None) => None,
+        };
+        #[cfg(not(feature = "redis"))]
+        let cache = self.cache;
+
+        let cache =
+            cache.ok_or_else(|| WechatError::Config("no cache backend configured".to_string()))?;
+
+        let has_http_settings = self.timeout.is_some()
+            || self.connect_timeout.is_some()
+            || self.proxy.is_some()
+            || self.user_agent.is_some();
+
+        let client = match self.client {
+            Some(_) if has_http_settings => {
+                return Err(WechatError::Config(
+                    "HTTP settings cannot be combined with an existing client".to_string(),
+                ));
+            }
+            Some(client) => client,
+            None => {
+                let mut builder = Client::builder();
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(proxy_url) = self.proxy {
+                    builder = builder.proxy(Proxy::all(&proxy_url)?);
+                }
+                if let Some(user_agent) = self.user_agent {
+                    builder = builder.user_agent(user_agent);
+                }
+                builder.build()?
+            }
+        };
+
+        Ok(OfficialAccount {
+            config: self.config,
+            cache,
+            client,
+            refresh_lock: Mutex::new(()),
+            token_policy: self.token_policy,
+            namespace: self.namespace,
+            api_base_url: parse_base_url(&self.api_base_url)?,
+            open_base_url: parse_base_url(&self.open_base_url)?,
+            mp_base_url: parse_base_url(&self.mp_base_url)?,
+        })
+    }
+}
+
+/// Checks that `base_url` is an absolute http(s) URL and strips the trailing
+/// slash, so that endpoint paths can be appended to it.
+fn parse_base_url(base_url: &str) -> Result<String> {
+    let url = Url::parse(base_url)
+        .map_err(|e| WechatError::Config(format!("invalid base URL {:?}: {}", base_url, e)))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(WechatError::Config(format!(
+            "invalid base URL {:?}: scheme must be http or https",
+            base_url
+        )));
+    }
+
+    Ok(base_url.trim_end_matches('/').to_string())
+}
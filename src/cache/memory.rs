@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::Cache;
+use crate::Result;
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::MemoryCache;
+    use crate::cache::Cache;
+
+    #[tokio::test]
+    async fn get_set_delete() {
+        let cache = MemoryCache::new();
+
+        assert_eq!(cache.get("key").await.unwrap(), None);
+
+        cache
+            .set("key", "value", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(cache.get("key").await.unwrap().as_deref(), Some("value"));
+        assert!(cache.ttl("key").await.unwrap().unwrap() <= Duration::from_secs(60));
+
+        cache.delete("key").await.unwrap();
+        assert_eq!(cache.get("key").await.unwrap(), None);
+        assert_eq!(cache.ttl("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn expiry() {
+        let cache = MemoryCache::new();
+
+        cache
+            .set("key", "value", Duration::from_millis(10))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(cache.get("key").await.unwrap(), None);
+        assert!(
+            cache
+                .set_nx("key", "other", Duration::from_secs(1))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn sweep_expired_entries() {
+        let cache = MemoryCache::new();
+
+        for i in 0..=super::SWEEP_THRESHOLD {
+            cache
+                .set(&format!("dedup:{}", i), "1", Duration::ZERO)
+                .await
+                .unwrap();
+        }
+        assert_eq!(cache.len(), super::SWEEP_THRESHOLD + 1);
+
+        assert!(
+            cache
+                .set_nx("key", "value", Duration::from_secs(60))
+                .await
+                .unwrap()
+        );
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries.contains_key("key"));
+    }
+
+    #[tokio::test]
+    async fn lock_semantics() {
+        let cache = MemoryCache::new();
+        let ttl = Duration::from_secs(60);
+
+        assert!(cache.set_nx("lock", "a", ttl).await.unwrap());
+        assert!(!cache.set_nx("lock", "b", ttl).await.unwrap());
+
+        assert!(!cache.delete_if_eq("lock", "b").await.unwrap());
+        assert!(cache.delete_if_eq("lock", "a").await.unwrap());
+        assert!(cache.set_nx("lock", "b", ttl).await.unwrap());
+    }
+}
+
+/// An in-process [`Cache`] with per-key expiry.
+///
+/// Useful for tests and single-node tools. Entries are not shared between
+/// processes, so the token refresh lock only protects a single process.
+///
+/// An expired entry is dropped when its key is next used, and all expired
+/// entries are swept once the cache holds more than 10 000 keys.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+/// Number of entries above which writes drop every expired entry.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+#[derive(Debug)]
+struct Entry {
+    value: String,
+    expires_at: Instant,
+}
+
+impl Entry {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires_at > now
+    }
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Runs `f` on the entry map after evicting `key` if it has expired.
+    fn with_entries<T>(&self, key: &str, f: impl FnOnce(&mut HashMap<String, Entry>) -> T) -> T {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        let now = Instant::now();
+        if entries.get(key).is_some_and(|entry| !entry.is_live(now)) {
+            entries.remove(key);
+        }
+
+        f(&mut entries)
+    }
+}
+
+/// Inserts `key`, first dropping every expired entry once the map has grown
+/// past [`SWEEP_THRESHOLD`].
+fn insert(entries: &mut HashMap<String, Entry>, key: &str, value: &str, ttl: Duration) {
+    let now = Instant::now();
+    if entries.len() > SWEEP_THRESHOLD {
+        entries.retain(|_, entry| entry.is_live(now));
+    }
+
+    entries.insert(
+        key.to_string(),
+        Entry {
+            value: value.to_string(),
+            expires_at: now + ttl,
+        },
+    );
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.with_entries(key, |entries| entries.get(key).map(|e| e.value.clone())))
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        self.with_entries(key, |entries| insert(entries, key, value, ttl));
+
+        Ok(())
+    }
+
+    async fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool> {
+        Ok(self.with_entries(key, |entries| {
+            if entries.contains_key(key) {
+                return false;
+            }
+
+            insert(entries, key, value, ttl);
+            true
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.with_entries(key, |entries| entries.remove(key));
+
+        Ok(())
+    }
+
+    async fn delete_if_eq(&self, key: &str, value: &str) -> Result<bool> {
+        Ok(self.with_entries(key, |entries| {
+            if entries.get(key).is_some_and(|e| e.value == value) {
+                entries.remove(key);
+                return true;
+            }
+            false
+        }))
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>> {
+        Ok(self.with_entries(key, |entries| {
+            entries
+                .get(key)
+                .map(|e| e.expires_at.saturating_duration_since(Instant::now()))
+        }))
+    }
+}
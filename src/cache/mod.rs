@@ -0,0 +1,47 @@
+//! Storage backends for access tokens and other short-lived SDK state.
+//!
+//! The SDK only talks to storage through the [`Cache`] trait, so any backend
+//! (Postgres, memcached, ...) can be plugged in by implementing it.
+
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+use async_trait::async_trait;
+use std::time::Duration;
+
+use crate::Result;
+
+pub use memory::MemoryCache;
+#[cfg(feature = "redis")]
+pub use redis::RedisCache;
+
+/// A key/value store with per-key expiry.
+///
+/// Implementations must be safe to share between workers: `set_nx` and
+/// `delete_if_eq` are used as a distributed lock around token refreshes, so
+/// they must be atomic with respect to other callers of the same backend.
+#[async_trait]
+pub trait Cache: Send + Sync {
+    /// Returns the value stored under `key`, if it exists and has not expired.
+    async fn get(&self, key: &str) -> Result<Option<String>>;
+
+    /// Stores `value` under `key` for `ttl`.
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> Result<()>;
+
+    /// Stores `value` under `key` for `ttl` only if `key` does not exist yet.
+    ///
+    /// Returns `true` if the value was stored.
+    async fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool>;
+
+    /// Removes `key`.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Removes `key` only if it currently holds `value`.
+    ///
+    /// Returns `true` if the key was removed.
+    async fn delete_if_eq(&self, key: &str, value: &str) -> Result<bool>;
+
+    /// Returns the remaining lifetime of `key`, or `None` if it does not exist.
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>>;
+}
@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use deadpool_redis::{Pool, Runtime, redis::cmd};
+use std::time::Duration;
+
+use super::Cache;
+use crate::{Result, WechatError};
+
+/// Deletes the key only if it still holds the expected value.
+const DELETE_IF_EQ_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end";
+
+/// A [`Cache`] backed by a Redis connection pool.
+#[derive(Clone)]
+pub struct RedisCache {
+    pool: Pool,
+}
+
+impl RedisCache {
+    /// Wraps an existing Redis pool.
+    pub fn new(pool: Pool) -> Self {
+        RedisCache { pool }
+    }
+
+    /// Creates a pool for the given Redis connection string.
+    ///
+    /// No connection is opened until the cache is first used.
+    pub fn from_url(redis_url: impl Into<String>) -> Result<Self> {
+        let pool = deadpool_redis::Config::from_url(redis_url)
+            .create_pool(Some(Runtime::Tokio1))
+            .map_err(|e| WechatError::Config(format!("failed to create Redis pool: {}", e)))?;
+
+        Ok(RedisCache { pool })
+    }
+
+    /// Returns the underlying Redis pool.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl Cache for RedisCache {
+    async fn get(&self, key: &str) -> Result<Option<String>> {
+        let mut rdb = self.pool.get().await?;
+
+        Ok(cmd("GET").arg(key).query_async(&mut rdb).await?)
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        let mut rdb = self.pool.get().await?;
+
+        cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async::<()>(&mut rdb)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool> {
+        let mut rdb = self.pool.get().await?;
+
+        let stored: Option<String> = cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async(&mut rdb)
+            .await?;
+
+        Ok(stored.is_some())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let mut rdb = self.pool.get().await?;
+
+        cmd("DEL").arg(key).query_async::<()>(&mut rdb).await?;
+
+        Ok(())
+    }
+
+    async fn delete_if_eq(&self, key: &str, value: &str) -> Result<bool> {
+        let mut rdb = self.pool.get().await?;
+
+        let deleted: i64 = cmd("EVAL")
+            .arg(DELETE_IF_EQ_SCRIPT)
+            .arg(1)
+            .arg(key)
+            .arg(value)
+            .query_async(&mut rdb)
+            .await?;
+
+        Ok(deleted == 1)
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>> {
+        let mut rdb = self.pool.get().await?;
+
+        // -2: the key does not exist, -1: the key has no expiry
+        let millis: i64 = cmd("PTTL").arg(key).query_async(&mut rdb).await?;
+        match millis {
+            -2 => Ok(None),
+            -1 => Ok(Some(Duration::MAX)),
+            millis => Ok(Some(Duration::from_millis(millis as u64))),
+        }
+    }
+}
@@ -1,3 +1,15 @@
 pub mod keys {
-    pub(crate) const GLOBAL_TOKEN: &str = "async-wechat:global:token";
+    /// Prefix of every cache key, followed by the appid.
+    pub(crate) const DEFAULT_NAMESPACE: &str = "async-wechat";
+
+    pub(crate) const TOKEN: &str = "token";
+    pub(crate) const TOKEN_LOCK: &str = "token:lock";
+    pub(crate) const OAUTH_TOKEN: &str = "oauth:token";
+    pub(crate) const DEDUP: &str = "dedup";
+}
+
+pub mod urls {
+    pub(crate) const API_BASE_URL: &str = "https://api.weixin.qq.com";
+    pub(crate) const OPEN_BASE_URL: &str = "https://open.weixin.qq.com";
+    pub(crate) const MP_BASE_URL: &str = "https://mp.weixin.qq.com";
 }
@@ -0,0 +1,161 @@
+#[cfg(feature = "redis")]
+use deadpool_redis::{PoolError, redis::RedisError};
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::WechatError;
+    use crate::cache::MemoryCache;
+    use crate::{Config, OfficialAccount, official_account::qrcode::build_tmp_qr_request};
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    fn assert_send<T: Send>(_: T) {}
+
+    #[test]
+    fn error_is_send_sync() {
+        assert_send_sync::<WechatError>();
+    }
+
+    #[test]
+    fn token_invalid_errcodes() {
+        for errcode in [40001, 40014, 42001] {
+            let err = WechatError::Api {
+                errcode,
+                errmsg: String::new(),
+            };
+            assert!(err.is_token_invalid());
+        }
+
+        let err = WechatError::Api {
+            errcode: 45009,
+            errmsg: String::new(),
+        };
+        assert!(!err.is_token_invalid());
+    }
+
+    #[tokio::test]
+    async fn futures_are_send() {
+        let config = Config {
+            appid: "wx0000000000000000".to_string(),
+            app_secret: "secret".to_string(),
+            token: "wechat".to_string(),
+            encoding_aes_key: None,
+        };
+        let account = OfficialAccount::with_cache(config, Arc::new(MemoryCache::new()));
+
+        assert_send(account.token());
+        assert_send(account.get_oauth2_token("code".to_string()));
+        assert_send(account.get_userinfo("openid".to_string()));
+        assert_send(account.get_qr_ticket(build_tmp_qr_request(60, 1u32)));
+        assert_send(account.clear_quota());
+        assert_send(account.delete_menu());
+        assert_send(account.get_user_by_open_id("openid"));
+        assert_send(account.get_user_list(None));
+        assert_send(account.batch_get_user_info(&[]));
+    }
+}
+
+/// Errors returned by the WeChat SDK.
+///
+/// The type is `Send + Sync + 'static`, so futures returned by the SDK can be
+/// moved across tasks (e.g. `tokio::spawn`) and handler boundaries.
+#[derive(Debug, thiserror::Error)]
+pub enum WechatError {
+    /// The HTTP request failed or returned a non-success status.
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// A request URL could not be built.
+    #[error("invalid url: {0}")]
+    Url(#[from] url::ParseError),
+
+    /// A Redis command failed.
+    #[cfg(feature = "redis")]
+    #[error("redis error: {0}")]
+    Redis(#[from] RedisError),
+
+    /// A connection could not be obtained from the Redis pool.
+    #[cfg(feature = "redis")]
+    #[error("redis pool error: {0}")]
+    Pool(#[from] PoolError),
+
+    /// A custom cache backend failed.
+    #[error("cache error: {0}")]
+    Cache(Box<dyn std::error::Error + Send + Sync>),
+
+    /// The SDK was configured with invalid settings.
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    /// A JSON payload could not be serialized or deserialized.
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// A TOML document could not be deserialized.
+    #[error("toml error: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    /// An XML payload could not be deserialized.
+    #[error("xml decode error: {0}")]
+    XmlDecode(#[from] quick_xml::DeError),
+
+    /// An XML payload could not be serialized.
+    #[error("xml encode error: {0}")]
+    XmlEncode(#[from] quick_xml::SeError),
+
+    /// WeChat answered with a non-zero `errcode`.
+    #[error("wechat api error: errcode={errcode}, errmsg={errmsg}")]
+    Api { errcode: i64, errmsg: String },
+
+    /// Another worker holds the refresh lock and no new access token appeared
+    /// in time.
+    #[error("timed out waiting for the access token refresh")]
+    TokenRefreshTimeout,
+
+    /// The signature of a callback request does not match.
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    /// An argument is outside the limits documented by WeChat.
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    /// A required query parameter of a callback request is missing.
+    #[error("missing query parameter {0}")]
+    MissingParameter(&'static str),
+
+    /// An encrypted message could not be decrypted or encrypted.
+    #[error("message crypto error: {0}")]
+    Crypto(String),
+
+    /// No OAuth2 access token is cached for the given openid.
+    #[error("oauth2 access token not found for openid {0}")]
+    OAuthTokenNotFound(String),
+}
+
+impl WechatError {
+    /// Returns the WeChat `errcode` if this is an API error.
+    ///
+    /// ```
+    /// use async_wechat::WechatError;
+    ///
+    /// let err = WechatError::Api { errcode: 45009, errmsg: "reach max api daily quota limit".into() };
+    /// assert_eq!(err.errcode(), Some(45009));
+    /// ```
+    pub fn errcode(&self) -> Option<i64> {
+        match self {
+            WechatError::Api { errcode, .. } => Some(*errcode),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if WeChat rejected the access token: 40001 (invalid
+    /// credential), 40014 (invalid access_token) or 42001 (access_token expired).
+    pub fn is_token_invalid(&self) -> bool {
+        matches!(self.errcode(), Some(40001 | 40014 | 42001))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, WechatError>;
@@ -1,15 +1,35 @@
+// Tests sit at the top of each module, ahead of the items they cover.
+#![allow(clippy::items_after_test_module)]
+
+mod builder;
+pub mod cache;
 mod constants;
+pub mod error;
 pub mod official_account;
+#[cfg(test)]
+mod test_util;
+
+pub use error::{Result, WechatError};
 
-use deadpool_redis::{Pool, Runtime};
+use cache::Cache;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tokio::sync::Mutex;
+
+pub use builder::OfficialAccountBuilder;
+pub use official_account::token::TokenPolicy;
 
 pub struct OfficialAccount {
     config: Config,
-    rdb_pool: Arc<Pool>,
+    cache: Arc<dyn Cache>,
     client: Client,
+    refresh_lock: Mutex<()>,
+    token_policy: TokenPolicy,
+    namespace: String,
+    api_base_url: String,
+    open_base_url: String,
+    mp_base_url: String,
 }
 
 pub struct Config {
@@ -19,6 +39,49 @@ pub struct Config {
     pub encoding_aes_key: Option<String>,
 }
 
+impl Config {
+    /// Checks the configuration against the formats issued by the WeChat
+    /// console.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::Config` if `appid` is not `wx` followed by 16
+    ///   alphanumeric characters, if `app_secret` is empty, if `token` is not 3
+    ///   to 32 alphanumeric characters, or if `encoding_aes_key` is not 43
+    ///   alphanumeric characters.
+    pub fn validate(&self) -> Result<()> {
+        let is_alphanumeric = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric());
+
+        if !(self.appid.len() == 18 && self.appid.starts_with("wx") && is_alphanumeric(&self.appid))
+        {
+            return Err(WechatError::Config(format!(
+                "invalid appid {:?}",
+                self.appid
+            )));
+        }
+
+        if self.app_secret.is_empty() {
+            return Err(WechatError::Config("app_secret is empty".to_string()));
+        }
+
+        if !((3..=32).contains(&self.token.len()) && is_alphanumeric(&self.token)) {
+            return Err(WechatError::Config(
+                "token must be 3 to 32 alphanumeric characters".to_string(),
+            ));
+        }
+
+        if let Some(key) = &self.encoding_aes_key {
+            if !(key.len() == 43 && is_alphanumeric(key)) {
+                return Err(WechatError::Config(
+                    "encoding_aes_key must be 43 alphanumeric characters".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TokenResponse {
     pub access_token: String,
@@ -37,20 +100,77 @@ impl OfficialAccount {
     /// # Returns
     ///
     /// A new instance of the OfficialAccount struct.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the Redis URL is invalid. Use [`OfficialAccount::builder`] for a
+    /// fallible, validated construction.
+    #[cfg(feature = "redis")]
     pub fn new(conf: Config, redis_url: String) -> Self {
-        let pool_config = deadpool_redis::Config::from_url(redis_url);
-
-        let rdb_pool = match pool_config.create_pool(Some(Runtime::Tokio1)) {
-            Ok(pool) => Arc::new(pool),
+        let cache = match cache::RedisCache::from_url(redis_url) {
+            Ok(cache) => cache,
             Err(err) => {
-                panic!("Failed to create Redis pool: {}", err);
+                panic!("{}", err);
             }
         };
 
+        Self::with_cache(conf, Arc::new(cache))
+    }
+
+    /// Creates a new instance of the OfficialAccount struct that stores tokens
+    /// in the given cache backend.
+    pub fn with_cache(conf: Config, cache: Arc<dyn Cache>) -> Self {
         OfficialAccount {
             config: conf,
-            rdb_pool,
+            cache,
             client: Client::new(),
+            refresh_lock: Mutex::new(()),
+            token_policy: TokenPolicy::default(),
+            namespace: constants::keys::DEFAULT_NAMESPACE.to_string(),
+            api_base_url: constants::urls::API_BASE_URL.to_string(),
+            open_base_url: constants::urls::OPEN_BASE_URL.to_string(),
+            mp_base_url: constants::urls::MP_BASE_URL.to_string(),
         }
     }
+
+    /// Returns a builder that validates the configuration and accepts an
+    /// existing HTTP client or Redis pool.
+    pub fn builder(conf: Config) -> OfficialAccountBuilder {
+        OfficialAccountBuilder::new(conf)
+    }
+
+    /// Sets how long access tokens are cached and when they are refreshed.
+    pub fn with_token_policy(mut self, policy: TokenPolicy) -> Self {
+        self.token_policy = policy;
+        self
+    }
+
+    /// Sets the prefix of every cache key (`async-wechat` by default).
+    ///
+    /// Keys are laid out as `{namespace}:{appid}:{name}`, so several accounts
+    /// can share one cache.
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Returns the URL of the API endpoint at `path`.
+    pub(crate) fn api_url(&self, path: &str) -> String {
+        format!("{}{}", self.api_base_url, path)
+    }
+
+    /// Returns the URL of the `open.weixin.qq.com` page at `path`.
+    pub(crate) fn open_url(&self, path: &str) -> String {
+        format!("{}{}", self.open_base_url, path)
+    }
+
+    /// Returns the URL of the `mp.weixin.qq.com` page at `path`.
+    pub(crate) fn mp_url(&self, path: &str) -> String {
+        format!("{}{}", self.mp_base_url, path)
+    }
+
+    /// Returns the cache key for `name`, scoped to this account.
+    pub(crate) fn cache_key(&self, name: &str) -> String {
+        format!("{}:{}:{}", self.namespace, self.config.appid, name)
+    }
 }
@@ -1,12 +1,16 @@
-use crate::OfficialAccount;
+use crate::constants::keys;
+use crate::{OfficialAccount, Result, WechatError};
+
+use super::response;
 
-use deadpool_redis::redis::cmd;
 use serde::{Deserialize, Serialize};
-use url::{Url, form_urlencoded};
+use url::form_urlencoded;
 
-pub(crate) const OAUTH2_URL: &str = "https://open.weixin.qq.com/connect/oauth2/authorize";
+pub(crate) const OAUTH2_PATH: &str = "/connect/oauth2/authorize";
+pub(crate) const OAUTH2_TOKEN_PATH: &str = "/sns/oauth2/access_token";
+pub(crate) const USERINFO_PATH: &str = "/sns/userinfo";
 
-#[cfg(test)]
+#[cfg(all(test, feature = "redis"))]
 mod tests {
     use std::env;
 
@@ -70,13 +74,15 @@ pub struct UserInfoResponse {
 
 impl OfficialAccount {
     /// [获取跳转的url地址](https://developers.weixin.qq.com/doc/offiaccount/OA_Web_Apps/Wechat_webpage_authorization.html)
+    ///
+    /// The URL is appended to the configured open base URL as is, without
+    /// parsing it.
     pub fn get_redirect_url(
         &self,
         redirect_uri: String,
         scope: String,
         state: Option<String>,
     ) -> String {
-        let mut url = Url::parse(OAUTH2_URL).unwrap();
         let query = form_urlencoded::Serializer::new(String::new())
             .append_pair("appid", &self.config.appid)
             .append_pair("redirect_uri", &redirect_uri)
@@ -85,8 +91,7 @@ impl OfficialAccount {
             .append_pair("state", state.as_deref().unwrap_or(""))
             .finish();
 
-        url.set_query(Some(&query));
-        format!("{}#wechat_redirect", url.to_string())
+        format!("{}?{}#wechat_redirect", self.open_url(OAUTH2_PATH), query)
     }
 
     /// [Exchanges the given authorization code for an access token using the WeChat API](https://developers.weixin.qq.com/doc/offiaccount/Basic_Information/get_oauth2_token.html)
@@ -102,41 +107,39 @@ impl OfficialAccount {
     ///
     /// # Returns
     ///
-    /// * A `Result` containing an `AccessTokenResponse` on success, or a boxed error on failure.
+    /// * A `Result` containing an `AccessTokenResponse` on success, or a `WechatError` on failure.
     ///
     /// # Errors
     ///
-    /// * Returns an error if the HTTP request fails or returns a non-success status, or if
-    ///   the response cannot be deserialized into an `AccessTokenResponse`.
-    pub async fn get_oauth2_token(
-        &self,
-        code: String,
-    ) -> Result<AccessTokenResponse, Box<dyn std::error::Error>> {
+    /// * Returns an error if the HTTP request fails or returns a non-success status, if
+    ///   WeChat answers with a non-zero `errcode`, or if the response cannot be
+    ///   deserialized into an `AccessTokenResponse`.
+    pub async fn get_oauth2_token(&self, code: String) -> Result<AccessTokenResponse> {
         let url = format!(
-            "https://api.weixin.qq.com/sns/oauth2/access_token?appid={}&secret={}&code={}&grant_type=authorization_code",
-            self.config.appid, self.config.app_secret, code
+            "{}?appid={}&secret={}&code={}&grant_type=authorization_code",
+            self.api_url(OAUTH2_TOKEN_PATH),
+            self.config.appid,
+            self.config.app_secret,
+            code
         );
 
-        println!("url: {}", url);
-
         let response = self.client.get(&url).send().await?;
-        let at: AccessTokenResponse = response.json::<AccessTokenResponse>().await?;
+        let at: AccessTokenResponse = response::decode(response).await?;
 
-        let mut rdb = self.rdb_pool.get().await.unwrap();
-        cmd("SETEX")
-            .arg(&at.open_id)
-            .arg(24 * 60)
-            .arg(serde_json::to_string(&at).unwrap())
-            .query_async::<()>(&mut rdb)
-            .await
-            .unwrap();
+        self.cache
+            .set(
+                &self.oauth_token_key(&at.open_id),
+                &serde_json::to_string(&at)?,
+                self.token_policy.ttl(at.expires_in),
+            )
+            .await?;
 
         Ok(at)
     }
 
     /// Retrieves user information from WeChat API using the provided `openid`.
     ///
-    /// This function fetches the access token from the Redis database associated
+    /// This function fetches the access token from the cache associated
     /// with the given `openid`, constructs a request to the WeChat API to get
     /// user information, and processes the response.
     ///
@@ -146,65 +149,35 @@ impl OfficialAccount {
     ///
     /// # Returns
     ///
-    /// * A `Result` containing a `UserInfoResponse` on success, or a boxed error on failure.
+    /// * A `Result` containing a `UserInfoResponse` on success, or a `WechatError` on failure.
     ///
     /// # Errors
     ///
-    /// * Returns an error if the access token is not found in Redis, if the HTTP
-    /// request fails or returns a non-success status, or if the response cannot be
-    /// deserialized into a `UserInfoResponse`.
-    pub async fn get_userinfo(
-        &self,
-        openid: String,
-    ) -> Result<UserInfoResponse, Box<dyn std::error::Error>> {
-        let mut rdb = self.rdb_pool.get().await?;
-
-        let access_token = match cmd("GET")
-            .arg(&openid)
-            .query_async::<String>(&mut rdb)
-            .await
-        {
-            Ok(access_token) => access_token,
-            Err(_) => {
-                return Err("access token not found".into());
+    /// * Returns an error if the access token is not found in the cache, if the HTTP
+    ///   request fails or returns a non-success status, if WeChat answers with a
+    ///   non-zero `errcode`, or if the response cannot be deserialized into a
+    ///   `UserInfoResponse`.
+    pub async fn get_userinfo(&self, openid: String) -> Result<UserInfoResponse> {
+        let access_token = match self.cache.get(&self.oauth_token_key(&openid)).await? {
+            Some(bytes) => serde_json::from_str::<AccessTokenResponse>(&bytes)?.access_token,
+            None => {
+                return Err(WechatError::OAuthTokenNotFound(openid));
             }
         };
 
         let url = format!(
-            "https://api.weixin.qq.com/sns/userinfo?access_token={}&openid={}",
-            access_token, openid
+            "{}?access_token={}&openid={}",
+            self.api_url(USERINFO_PATH),
+            access_token,
+            openid
         );
 
         let response = self.client.get(&url).send().await?;
-        let status = response.status();
-
-        // 检查 HTTP 响应状态码
-        if !status.is_success() {
-            return Err(format!("HTTP error: {}", status).into());
-        }
-
-        // 处理微信 API 响应
-        let response_text = response.text().await?;
-        if let Ok(api_error) = serde_json::from_str::<BasicResponse>(&response_text) {
-            return Err(format!(
-                "Wechat API error: code={}, message={}",
-                api_error.errcode, api_error.errmsg
-            )
-            .into());
-        }
-
-        // 尝试解析为 `UserInfoResponse`
-        let user_info: UserInfoResponse = match serde_json::from_str(&response_text) {
-            Ok(info) => info,
-            Err(e) => {
-                eprintln!(
-                    "Failed to deserialize UserInfoResponse: {}, response: {}",
-                    e, response_text
-                );
-                return Err(format!("Error decoding response body: {}", e).into());
-            }
-        };
+        response::decode(response).await
+    }
 
-        Ok(user_info)
+    /// Returns the cache key of the OAuth2 access token issued for `openid`.
+    fn oauth_token_key(&self, openid: &str) -> String {
+        self.cache_key(&format!("{}:{}", keys::OAUTH_TOKEN, openid))
     }
 }
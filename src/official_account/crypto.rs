@@ -0,0 +1,236 @@
+use aes::Aes256;
+use base64::{
+    Engine, alphabet,
+    engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig, STANDARD},
+};
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::NoPadding};
+use serde::Deserialize;
+
+use crate::{OfficialAccount, Result, WechatError};
+
+use super::signature::msg_signature;
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+/// WeChat pads plaintext to a multiple of 32 bytes, not the AES block size.
+const PAD_BLOCK_SIZE: usize = 32;
+
+/// `EncodingAESKey` is 43 base64 characters whose last one usually carries
+/// non-zero trailing bits, which the strict standard engine rejects.
+const KEY_ENGINE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_allow_trailing_bits(true),
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{EncryptedEnvelope, MessageCrypto};
+    use crate::test_util::{ENCODING_AES_KEY, ENCRYPTED};
+
+    const APPID: &str = "wx1234567890abcdef";
+
+    fn crypto() -> MessageCrypto {
+        MessageCrypto::new("wechat", APPID, ENCODING_AES_KEY).unwrap()
+    }
+
+    #[test]
+    fn decrypt() {
+        let crypto = crypto();
+
+        crypto
+            .verify(
+                "aba6dcd6bfb4cc5aed4a8b527503579881712f3d",
+                "1409735669",
+                "1320562132",
+                ENCRYPTED,
+            )
+            .unwrap();
+
+        let xml = crypto.decrypt(ENCRYPTED).unwrap();
+        assert!(xml.starts_with("<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName>"));
+        assert!(xml.ends_with("<MsgId>1234567890123456</MsgId></xml>"));
+    }
+
+    #[test]
+    fn decrypt_rejects_other_appid() {
+        let crypto = MessageCrypto::new("wechat", "wxffffffffffffffff", ENCODING_AES_KEY).unwrap();
+        assert!(crypto.decrypt(ENCRYPTED).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_bad_signature() {
+        let crypto = crypto();
+        assert!(
+            crypto
+                .verify("bad", "1409735669", "1320562132", ENCRYPTED)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn encrypt_reply_roundtrip() {
+        let crypto = crypto();
+        let reply = "<xml><Content><![CDATA[你好 😀]]></Content></xml>";
+
+        let envelope = crypto
+            .encrypt_reply(reply, "1409735669", "1320562132")
+            .unwrap();
+        assert!(envelope.contains("<TimeStamp>1409735669</TimeStamp>"));
+
+        let envelope = quick_xml::de::from_str::<EncryptedEnvelope>(&envelope).unwrap();
+        let signature = envelope.msg_signature.unwrap();
+        crypto
+            .verify(&signature, "1409735669", "1320562132", &envelope.encrypt)
+            .unwrap();
+        assert_eq!(crypto.decrypt(&envelope.encrypt).unwrap(), reply);
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert!(MessageCrypto::new("wechat", APPID, "too-short").is_err());
+    }
+}
+
+/// The `<xml>` envelope of an encrypted message.
+///
+/// Requests carry `ToUserName` and `Encrypt`; encrypted replies carry
+/// `Encrypt`, `MsgSignature`, `TimeStamp` and `Nonce`.
+#[derive(Debug, Deserialize)]
+#[serde(rename = "xml")]
+pub struct EncryptedEnvelope {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: Option<String>,
+    #[serde(rename = "Encrypt")]
+    pub encrypt: String,
+    #[serde(rename = "MsgSignature")]
+    pub msg_signature: Option<String>,
+}
+
+/// [消息加解密](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Message_encryption_and_decryption_instructions.html)
+/// for the "安全模式" and "兼容模式" of the callback endpoint.
+#[derive(Clone)]
+pub struct MessageCrypto {
+    token: String,
+    appid: String,
+    key: [u8; 32],
+}
+
+impl MessageCrypto {
+    /// Creates a new instance from the token, appid and 43-character
+    /// `EncodingAESKey` configured in the WeChat console.
+    pub fn new(token: &str, appid: &str, encoding_aes_key: &str) -> Result<Self> {
+        let key = KEY_ENGINE
+            .decode(format!("{}=", encoding_aes_key))
+            .ok()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .ok_or_else(|| WechatError::Config("invalid encoding_aes_key".to_string()))?;
+
+        Ok(MessageCrypto {
+            token: token.to_string(),
+            appid: appid.to_string(),
+            key,
+        })
+    }
+
+    /// Checks the `msg_signature` of an encrypted payload.
+    pub fn verify(
+        &self,
+        signature: &str,
+        timestamp: &str,
+        nonce: &str,
+        encrypt: &str,
+    ) -> Result<()> {
+        if msg_signature(&self.token, timestamp, nonce, encrypt) != signature {
+            return Err(WechatError::InvalidSignature);
+        }
+
+        Ok(())
+    }
+
+    /// Decrypts the `Encrypt` field and checks that it was sent to our appid.
+    pub fn decrypt(&self, encrypt: &str) -> Result<String> {
+        let invalid = |reason: &str| WechatError::Crypto(reason.to_string());
+
+        let mut buf = STANDARD
+            .decode(encrypt)
+            .map_err(|_| invalid("Encrypt is not valid base64"))?;
+
+        let plain = Aes256CbcDec::new_from_slices(&self.key, &self.key[..16])
+            .map_err(|_| invalid("invalid key length"))?
+            .decrypt_padded_mut::<NoPadding>(&mut buf)
+            .map_err(|_| invalid("ciphertext is not block aligned"))?;
+
+        // PKCS#7 with a 32-byte block
+        let pad = *plain.last().ok_or_else(|| invalid("empty plaintext"))? as usize;
+        if pad == 0 || pad > PAD_BLOCK_SIZE || pad > plain.len() {
+            return Err(invalid("invalid padding"));
+        }
+        let plain = &plain[..plain.len() - pad];
+
+        // random(16) + msg_len(4, big endian) + msg + appid
+        if plain.len() < 20 {
+            return Err(invalid("plaintext too short"));
+        }
+        let len = u32::from_be_bytes([plain[16], plain[17], plain[18], plain[19]]) as usize;
+        let rest = &plain[20..];
+        if len > rest.len() {
+            return Err(invalid("invalid message length"));
+        }
+
+        let (msg, appid) = rest.split_at(len);
+        if appid != self.appid.as_bytes() {
+            return Err(invalid("appid mismatch"));
+        }
+
+        String::from_utf8(msg.to_vec()).map_err(|_| invalid("message is not valid UTF-8"))
+    }
+
+    /// Encrypts `msg` into the base64 `Encrypt` field.
+    pub fn encrypt(&self, msg: &str) -> Result<String> {
+        let mut plain = Vec::with_capacity(msg.len() + self.appid.len() + 20 + PAD_BLOCK_SIZE);
+        plain.extend_from_slice(&rand::random::<[u8; 16]>());
+        plain.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+        plain.extend_from_slice(msg.as_bytes());
+        plain.extend_from_slice(self.appid.as_bytes());
+
+        let pad = PAD_BLOCK_SIZE - plain.len() % PAD_BLOCK_SIZE;
+        plain.extend(std::iter::repeat_n(pad as u8, pad));
+
+        let len = plain.len();
+        let encrypted = Aes256CbcEnc::new_from_slices(&self.key, &self.key[..16])
+            .map_err(|_| WechatError::Crypto("invalid key length".to_string()))?
+            .encrypt_padded_mut::<NoPadding>(&mut plain, len)
+            .map_err(|_| WechatError::Crypto("plaintext is not block aligned".to_string()))?;
+
+        Ok(STANDARD.encode(encrypted))
+    }
+
+    /// Encrypts a reply and wraps it into the
+    /// `Encrypt`/`MsgSignature`/`TimeStamp`/`Nonce` envelope.
+    pub fn encrypt_reply(&self, reply: &str, timestamp: &str, nonce: &str) -> Result<String> {
+        let encrypt = self.encrypt(reply)?;
+        let signature = msg_signature(&self.token, timestamp, nonce, &encrypt);
+
+        Ok(format!(
+            "<xml><Encrypt><![CDATA[{}]]></Encrypt><MsgSignature><![CDATA[{}]]></MsgSignature><TimeStamp>{}</TimeStamp><Nonce><![CDATA[{}]]></Nonce></xml>",
+            encrypt, signature, timestamp, nonce
+        ))
+    }
+}
+
+impl OfficialAccount {
+    /// Returns the message crypto for this account's `encoding_aes_key`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::Config` if no `encoding_aes_key` is configured or
+    ///   if it is not a valid `EncodingAESKey`.
+    pub fn message_crypto(&self) -> Result<MessageCrypto> {
+        let key = self.config.encoding_aes_key.as_deref().ok_or_else(|| {
+            WechatError::Config("encoding_aes_key is required for encrypted messages".to_string())
+        })?;
+
+        MessageCrypto::new(&self.config.token, &self.config.appid, key)
+    }
+}
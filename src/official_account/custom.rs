@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{OfficialAccount, Result};
+
+use super::message::{MsgType, WeChatResponse};
+use super::{core::BasicResponse, response};
+
+pub(crate) const CUSTOM_SEND_PATH: &str = "/cgi-bin/message/custom/send?access_token=";
+
+#[cfg(test)]
+mod tests {
+    use super::CustomMessage;
+    use crate::official_account::message::Article;
+    use crate::test_util::{MockServer, inbound};
+
+    #[test]
+    fn from_reply() {
+        let message = CustomMessage::from_reply(&inbound().plaintext("hello")).unwrap();
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"touser":"oUser","msgtype":"text","text":{"content":"hello"}}"#
+        );
+
+        let article = Article {
+            title: "title".to_string(),
+            description: "description".to_string(),
+            pic_url: "http://example.com/a.jpg".to_string(),
+            url: "http://example.com".to_string(),
+        };
+        let second = Article {
+            title: "second".to_string(),
+            ..article.clone()
+        };
+        let reply = inbound().news(vec![article, second]).unwrap();
+        let message = CustomMessage::from_reply(&reply).unwrap();
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"touser":"oUser","msgtype":"news","news":{"articles":[{"title":"title","description":"description","url":"http://example.com","picurl":"http://example.com/a.jpg"}]}}"#
+        );
+
+        let reply = inbound().image("media_id");
+        let message = CustomMessage::from_reply(&reply).unwrap();
+        assert_eq!(message.image.unwrap().media_id, "media_id");
+
+        assert!(CustomMessage::from_reply(&inbound().transfer_customer_service(None)).is_none());
+    }
+
+    #[tokio::test]
+    async fn send_custom_message() {
+        let server = MockServer::with_token(|_| r#"{"errcode":0,"errmsg":"ok"}"#.to_string()).await;
+
+        server
+            .account()
+            .send_custom_message(&CustomMessage::text("oUser", "hello"))
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        let send = requests
+            .iter()
+            .find(|req| req.target.starts_with("/cgi-bin/message/custom/send"))
+            .unwrap();
+        assert_eq!(
+            send.target,
+            "/cgi-bin/message/custom/send?access_token=ACCESS_TOKEN"
+        );
+        assert!(send.body.contains(r#""content":"hello""#));
+    }
+}
+
+/// A customer-service message, sent to a user who interacted with the
+/// account in the last 48 hours.
+///
+/// [客服消息](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Service_Center_messages.html)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomMessage {
+    pub touser: String,  // 普通用户openid
+    pub msgtype: String, // 消息类型
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<CustomText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<CustomMedia>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice: Option<CustomMedia>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video: Option<CustomVideo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub music: Option<CustomMusic>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub news: Option<CustomNews>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customservice: Option<CustomService>, // 以某个客服帐号来发消息
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomText {
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomMedia {
+    pub media_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomVideo {
+    pub media_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumb_media_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomMusic {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub musicurl: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hqmusicurl: Option<String>,
+    pub thumb_media_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomNews {
+    pub articles: Vec<CustomArticle>, // 图文消息条数限制在1条以内
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomArticle {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    pub picurl: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomService {
+    pub kf_account: String,
+}
+
+impl CustomMessage {
+    /// Creates a text message to `touser`.
+    pub fn text(touser: &str, content: &str) -> Self {
+        CustomMessage {
+            text: Some(CustomText {
+                content: content.to_string(),
+            }),
+            ..Self::empty(touser, MsgType::TEXT)
+        }
+    }
+
+    /// Converts a passive reply into the equivalent customer-service message,
+    /// sent to the user the reply was addressed to.
+    ///
+    /// Customer-service news carries a single article, so only the first
+    /// article of a news reply is sent. A video reply has no thumbnail, so the
+    /// video is sent without `thumb_media_id`.
+    ///
+    /// Returns `None` for `transfer_customer_service`, which has no
+    /// customer-service equivalent, and for a news reply without articles.
+    pub fn from_reply(reply: &WeChatResponse) -> Option<Self> {
+        let message = Self::empty(&reply.to_user_name, &reply.msg_type);
+
+        let message = match reply.msg_type.as_str() {
+            MsgType::TEXT => CustomMessage {
+                text: Some(CustomText {
+                    content: reply.content.clone().unwrap_or_default(),
+                }),
+                ..message
+            },
+            MsgType::IMAGE => CustomMessage {
+                image: Some(CustomMedia {
+                    media_id: reply.image.as_ref()?.media_id.clone(),
+                }),
+                ..message
+            },
+            MsgType::VOICE => CustomMessage {
+                voice: Some(CustomMedia {
+                    media_id: reply.voice.as_ref()?.media_id.clone(),
+                }),
+                ..message
+            },
+            MsgType::VIDEO => {
+                let video = reply.video.as_ref()?;
+                CustomMessage {
+                    video: Some(CustomVideo {
+                        media_id: video.media_id.clone(),
+                        thumb_media_id: None,
+                        title: video.title.clone(),
+                        description: video.description.clone(),
+                    }),
+                    ..message
+                }
+            }
+            MsgType::MUSIC => {
+                let music = reply.music.as_ref()?;
+                CustomMessage {
+                    music: Some(CustomMusic {
+                        title: music.title.clone(),
+                        description: music.description.clone(),
+                        musicurl: music.music_url.clone(),
+                        hqmusicurl: music.hq_music_url.clone(),
+                        thumb_media_id: music.thumb_media_id.clone(),
+                    }),
+                    ..message
+                }
+            }
+            MsgType::NEWS => {
+                let article = reply.articles.as_ref()?.items.first()?;
+                CustomMessage {
+                    news: Some(CustomNews {
+                        articles: vec![CustomArticle {
+                            title: article.title.clone(),
+                            description: article.description.clone(),
+                            url: article.url.clone(),
+                            picurl: article.pic_url.clone(),
+                        }],
+                    }),
+                    ..message
+                }
+            }
+            _ => return None,
+        };
+
+        Some(message)
+    }
+
+    fn empty(touser: &str, msgtype: &str) -> Self {
+        CustomMessage {
+            touser: touser.to_string(),
+            msgtype: msgtype.to_string(),
+            text: None,
+            image: None,
+            voice: None,
+            video: None,
+            music: None,
+            news: None,
+            customservice: None,
+        }
+    }
+}
+
+impl OfficialAccount {
+    /// [客服接口-发消息](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Service_Center_messages.html#%E5%AE%A2%E6%9C%8D%E6%8E%A5%E5%8F%A3-%E5%8F%91%E6%B6%88%E6%81%AF)
+    pub async fn send_custom_message(&self, message: &CustomMessage) -> Result<()> {
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(CUSTOM_SEND_PATH), token);
+            let response = self.client.post(url).json(message).send().await?;
+            response::decode::<BasicResponse>(response).await
+        })
+        .await?;
+
+        Ok(())
+    }
+}
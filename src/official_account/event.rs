@@ -0,0 +1,700 @@
+use quick_xml::de::from_str;
+use serde::{Deserialize, Deserializer};
+
+use super::message::EventType;
+use crate::Result;
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventMessage, QrScene};
+    use crate::test_util::inbound_xml;
+
+    fn event(body: &str) -> Event {
+        let xml = inbound_xml(&format!("<MsgType><![CDATA[event]]></MsgType>{}", body));
+        let message = EventMessage::from_xml(&xml).unwrap();
+        assert_eq!(message.from_user_name, "oUser");
+        message.event
+    }
+
+    #[test]
+    fn parse_subscribe() {
+        let Event::Subscribe(subscribe) = event(
+            "<Event><![CDATA[subscribe]]></Event><EventKey><![CDATA[qrscene_123123]]></EventKey><Ticket><![CDATA[TICKET]]></Ticket>",
+        ) else {
+            panic!("expected a subscribe event");
+        };
+        assert_eq!(subscribe.event_key.as_deref(), Some("qrscene_123123"));
+        assert_eq!(subscribe.ticket.as_deref(), Some("TICKET"));
+
+        let Event::Subscribe(subscribe) = event("<Event><![CDATA[subscribe]]></Event>") else {
+            panic!("expected a subscribe event");
+        };
+        assert_eq!(subscribe.event_key, None);
+    }
+
+    #[test]
+    fn qr_scene_from_subscribe() {
+        let scene = event(
+            "<Event><![CDATA[subscribe]]></Event><EventKey><![CDATA[qrscene_123123]]></EventKey><Ticket><![CDATA[TICKET]]></Ticket>",
+        )
+        .qr_scene()
+        .unwrap();
+        assert_eq!(
+            scene,
+            QrScene {
+                id: Some(123123),
+                str: None,
+                ticket: Some("TICKET".to_string()),
+                is_new_subscriber: true,
+            }
+        );
+    }
+
+    #[test]
+    fn qr_scene_from_scan() {
+        let scene = event(
+            "<Event><![CDATA[SCAN]]></Event><EventKey><![CDATA[login:8f2a]]></EventKey><Ticket><![CDATA[TICKET]]></Ticket>",
+        )
+        .qr_scene()
+        .unwrap();
+        assert_eq!(scene.id, None);
+        assert_eq!(scene.str.as_deref(), Some("login:8f2a"));
+        assert_eq!(scene.ticket.as_deref(), Some("TICKET"));
+        assert!(!scene.is_new_subscriber);
+    }
+
+    #[test]
+    fn qr_scene_without_qr_code() {
+        assert!(
+            event("<Event><![CDATA[subscribe]]></Event>")
+                .qr_scene()
+                .is_none()
+        );
+        assert!(
+            event("<Event><![CDATA[subscribe]]></Event><EventKey><![CDATA[]]></EventKey>")
+                .qr_scene()
+                .is_none()
+        );
+        assert!(
+            event("<Event><![CDATA[CLICK]]></Event><EventKey><![CDATA[123]]></EventKey>")
+                .qr_scene()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn parse_unsubscribe() {
+        let event = event("<Event><![CDATA[unsubscribe]]></Event>");
+        assert!(matches!(event, Event::Unsubscribe));
+        assert_eq!(event.name(), "unsubscribe");
+    }
+
+    #[test]
+    fn parse_location() {
+        let Event::Location(location) = event(
+            "<Event><![CDATA[LOCATION]]></Event><Latitude>23.137466</Latitude><Longitude>113.352425</Longitude><Precision>119.385040</Precision>",
+        ) else {
+            panic!("expected a location event");
+        };
+        assert_eq!(location.latitude, 23.137466);
+        assert_eq!(location.longitude, 113.352425);
+        assert_eq!(location.precision, 119.38504);
+    }
+
+    #[test]
+    fn parse_view() {
+        let Event::View(view) = event(
+            "<Event><![CDATA[VIEW]]></Event><EventKey><![CDATA[https://www.qq.com/]]></EventKey><MenuId>123</MenuId>",
+        ) else {
+            panic!("expected a view event");
+        };
+        assert_eq!(view.event_key, "https://www.qq.com/");
+        assert_eq!(view.menu_id.as_deref(), Some("123"));
+    }
+
+    #[test]
+    fn parse_scancode_waitmsg() {
+        let event = event(
+            "<Event><![CDATA[scancode_waitmsg]]></Event><EventKey><![CDATA[6]]></EventKey><ScanCodeInfo><ScanType><![CDATA[qrcode]]></ScanType><ScanResult><![CDATA[2]]></ScanResult></ScanCodeInfo>",
+        );
+        assert_eq!(event.name(), "scancode_waitmsg");
+
+        let Event::ScanCodeWaitMsg(scan) = event else {
+            panic!("expected a scancode_waitmsg event");
+        };
+        assert_eq!(scan.scan_code_info.scan_type, "qrcode");
+        assert_eq!(scan.scan_code_info.scan_result, "2");
+    }
+
+    #[test]
+    fn parse_pic_photo_or_album() {
+        let Event::PicPhotoOrAlbum(pics) = event(
+            "<Event><![CDATA[pic_photo_or_album]]></Event><EventKey><![CDATA[6]]></EventKey><SendPicsInfo><Count>2</Count><PicList><item><PicMd5Sum><![CDATA[5a75aaca956d97be686719218f275c6b]]></PicMd5Sum></item><item><PicMd5Sum><![CDATA[1b5f7c23b5bf75682a53e7b6d163e185]]></PicMd5Sum></item></PicList></SendPicsInfo>",
+        ) else {
+            panic!("expected a pic_photo_or_album event");
+        };
+        assert_eq!(pics.send_pics_info.count, 2);
+        assert_eq!(pics.send_pics_info.pic_list.len(), 2);
+        assert_eq!(
+            pics.send_pics_info.pic_list[1].pic_md5_sum,
+            "1b5f7c23b5bf75682a53e7b6d163e185"
+        );
+    }
+
+    #[test]
+    fn parse_location_select() {
+        let Event::LocationSelect(select) = event(
+            "<Event><![CDATA[location_select]]></Event><EventKey><![CDATA[6]]></EventKey><SendLocationInfo><Location_X><![CDATA[23]]></Location_X><Location_Y><![CDATA[113]]></Location_Y><Scale><![CDATA[15]]></Scale><Label><![CDATA[ 广州市海珠区客村艺苑路 106号]]></Label><Poiname><![CDATA[]]></Poiname></SendLocationInfo>",
+        ) else {
+            panic!("expected a location_select event");
+        };
+        assert_eq!(select.send_location_info.location_x, 23.0);
+        assert_eq!(select.send_location_info.scale, 15);
+    }
+
+    #[test]
+    fn parse_template_send_job_finish() {
+        let Event::TemplateSendJobFinish(finish) = event(
+            "<Event><![CDATA[TEMPLATESENDJOBFINISH]]></Event><MsgID>200163836</MsgID><Status><![CDATA[success]]></Status>",
+        ) else {
+            panic!("expected a TEMPLATESENDJOBFINISH event");
+        };
+        assert_eq!(finish.msg_id, 200163836);
+        assert_eq!(finish.status, "success");
+    }
+
+    #[test]
+    fn parse_mass_send_job_finish() {
+        let Event::MassSendJobFinish(finish) = event(
+            "<Event><![CDATA[MASSSENDJOBFINISH]]></Event><MsgID>1000001625</MsgID><Status><![CDATA[err(30003)]]></Status><TotalCount>0</TotalCount><FilterCount>0</FilterCount><SentCount>0</SentCount><ErrorCount>0</ErrorCount><CopyrightCheckResult><Count>2</Count><ResultList><item><ArticleIdx>1</ArticleIdx><UserDeclareState>0</UserDeclareState><AuditState>2</AuditState><OriginalArticleUrl><![CDATA[Url_1]]></OriginalArticleUrl><OriginalArticleType>1</OriginalArticleType><CanReprint>1</CanReprint><NeedReplaceContent>1</NeedReplaceContent><NeedShowReprintSource>1</NeedShowReprintSource></item><item><ArticleIdx>2</ArticleIdx><UserDeclareState>0</UserDeclareState><AuditState>2</AuditState><OriginalArticleUrl><![CDATA[Url_2]]></OriginalArticleUrl><OriginalArticleType>1</OriginalArticleType><CanReprint>1</CanReprint><NeedReplaceContent>1</NeedReplaceContent><NeedShowReprintSource>1</NeedShowReprintSource></item></ResultList><CheckState>2</CheckState></CopyrightCheckResult>",
+        ) else {
+            panic!("expected a MASSSENDJOBFINISH event");
+        };
+        assert_eq!(finish.msg_id, 1000001625);
+        assert_eq!(finish.status, "err(30003)");
+
+        let result = finish.copyright_check_result.unwrap();
+        assert_eq!(result.count, 2);
+        assert_eq!(result.check_state, 2);
+        assert_eq!(result.result_list.len(), 2);
+        assert_eq!(
+            result.result_list[1].original_article_url.as_deref(),
+            Some("Url_2")
+        );
+    }
+
+    #[test]
+    fn parse_subscribe_msg_popup_event() {
+        let Event::SubscribeMsgPopup(popup) = event(
+            "<Event><![CDATA[subscribe_msg_popup_event]]></Event><SubscribeMsgPopupEvent><List><TemplateId><![CDATA[VRR0UEO9VJOLs0MHlU0OilqX6MVFDwH3_3gz3Oc0NIc]]></TemplateId><SubscribeStatusString><![CDATA[accept]]></SubscribeStatusString><PopupScene>2</PopupScene></List><List><TemplateId><![CDATA[9nLIlbOQZC5Y89AZteFEux3WCXRRRG5Wfzkpssu4bLI]]></TemplateId><SubscribeStatusString><![CDATA[reject]]></SubscribeStatusString><PopupScene>2</PopupScene></List></SubscribeMsgPopupEvent>",
+        ) else {
+            panic!("expected a subscribe_msg_popup_event event");
+        };
+        assert_eq!(popup.list.len(), 2);
+        assert_eq!(popup.list[0].subscribe_status_string, "accept");
+        assert_eq!(popup.list[1].popup_scene, 2);
+    }
+
+    #[test]
+    fn parse_subscribe_msg_sent_event() {
+        let Event::SubscribeMsgSent(sent) = event(
+            "<Event><![CDATA[subscribe_msg_sent_event]]></Event><SubscribeMsgSentEvent><List><TemplateId><![CDATA[VRR0UEO9VJOLs0MHlU0OilqX6MVFDwH3_3gz3Oc0NIc]]></TemplateId><MsgID>1700827132819554304</MsgID><ErrorCode>0</ErrorCode><ErrorStatus><![CDATA[success]]></ErrorStatus></List></SubscribeMsgSentEvent>",
+        ) else {
+            panic!("expected a subscribe_msg_sent_event event");
+        };
+        assert_eq!(sent.list[0].msg_id, 1700827132819554304);
+        assert_eq!(sent.list[0].error_code, 0);
+    }
+
+    #[test]
+    fn parse_kf_switch_session() {
+        let Event::KfSwitchSession(switch) = event(
+            "<Event><![CDATA[kf_switch_session]]></Event><FromKfAccount><![CDATA[test1@test]]></FromKfAccount><ToKfAccount><![CDATA[test2@test]]></ToKfAccount>",
+        ) else {
+            panic!("expected a kf_switch_session event");
+        };
+        assert_eq!(switch.from_kf_account, "test1@test");
+        assert_eq!(switch.to_kf_account, "test2@test");
+    }
+
+    #[test]
+    fn parse_unknown() {
+        let Event::Unknown(unknown) = event("<Event><![CDATA[poi_check_notify]]></Event>") else {
+            panic!("expected an unknown event");
+        };
+        assert_eq!(unknown.event, "poi_check_notify");
+        assert!(unknown.raw.contains("poi_check_notify"));
+    }
+}
+
+/// An event pushed to the callback URL (`MsgType` is `event`).
+///
+/// [接收事件推送](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Receiving_event_pushes.html)
+#[derive(Debug, Clone)]
+pub struct EventMessage {
+    pub to_user_name: String,   // 微信号(公众号原始id)
+    pub from_user_name: String, // 发送方账号（一个OpenID）
+    pub create_time: u64,       // 消息创建时间 （整型）
+    pub event: Event,
+}
+
+/// The event-specific part of an [`EventMessage`], one variant per `Event`.
+#[derive(Debug, Clone)]
+pub enum Event {
+    Subscribe(SubscribeEvent),
+    Unsubscribe,
+    Scan(ScanEvent),
+    Location(LocationEvent),
+    Click(ClickEvent),
+    View(ViewEvent),
+    ScanCodePush(ScanCodeEvent),
+    ScanCodeWaitMsg(ScanCodeEvent),
+    PicSysPhoto(PicEvent),
+    PicPhotoOrAlbum(PicEvent),
+    PicWeixin(PicEvent),
+    LocationSelect(LocationSelectEvent),
+    ViewMiniProgram(ViewMiniProgramEvent),
+    TemplateSendJobFinish(TemplateSendJobFinishEvent),
+    MassSendJobFinish(MassSendJobFinishEvent),
+    SubscribeMsgPopup(SubscribeMsgPopupEvent),
+    SubscribeMsgChange(SubscribeMsgChangeEvent),
+    SubscribeMsgSent(SubscribeMsgSentEvent),
+    KfCreateSession(KfSessionEvent),
+    KfCloseSession(KfSessionEvent),
+    KfSwitchSession(KfSwitchSessionEvent),
+    /// An `Event` this SDK does not know yet.
+    Unknown(UnknownEvent),
+}
+
+/// 关注事件，扫描带参数二维码关注时带有 `EventKey` 和 `Ticket`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: Option<String>, // qrscene_为前缀，后面为二维码的参数值
+    #[serde(rename = "Ticket")]
+    pub ticket: Option<String>, // 二维码的ticket，可用来换取二维码图片
+}
+
+/// 已关注用户扫描带参数二维码事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: String, // 二维码的场景值
+    #[serde(rename = "Ticket")]
+    pub ticket: Option<String>,
+}
+
+/// 上报地理位置事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct LocationEvent {
+    #[serde(rename = "Latitude")]
+    pub latitude: f64, // 地理位置纬度
+    #[serde(rename = "Longitude")]
+    pub longitude: f64, // 地理位置经度
+    #[serde(rename = "Precision")]
+    pub precision: f64, // 地理位置精度
+}
+
+/// 点击菜单拉取消息事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClickEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: String, // 与自定义菜单接口中KEY值对应
+}
+
+/// 点击菜单跳转链接事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: String, // 设置的跳转URL
+    #[serde(rename = "MenuId")]
+    pub menu_id: Option<String>, // 个性化菜单id
+}
+
+/// 扫码推事件（`scancode_push` 和 `scancode_waitmsg`）
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanCodeEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: String,
+    #[serde(rename = "ScanCodeInfo")]
+    pub scan_code_info: ScanCodeInfo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanCodeInfo {
+    #[serde(rename = "ScanType")]
+    pub scan_type: String, // 扫描类型，一般是qrcode
+    #[serde(rename = "ScanResult")]
+    pub scan_result: String, // 扫描结果，即二维码对应的字符串信息
+}
+
+/// 发图事件（`pic_sysphoto`、`pic_photo_or_album` 和 `pic_weixin`）
+#[derive(Debug, Clone, Deserialize)]
+pub struct PicEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: String,
+    #[serde(rename = "SendPicsInfo")]
+    pub send_pics_info: SendPicsInfo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SendPicsInfo {
+    #[serde(rename = "Count")]
+    pub count: u32, // 发送的图片数量
+    #[serde(rename = "PicList", default, deserialize_with = "items")]
+    pub pic_list: Vec<PicItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PicItem {
+    #[serde(rename = "PicMd5Sum")]
+    pub pic_md5_sum: String, // 图片的MD5值
+}
+
+/// 弹出地理位置选择器事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct LocationSelectEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: String,
+    #[serde(rename = "SendLocationInfo")]
+    pub send_location_info: SendLocationInfo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SendLocationInfo {
+    #[serde(rename = "Location_X")]
+    pub location_x: f64, // 纬度
+    #[serde(rename = "Location_Y")]
+    pub location_y: f64, // 经度
+    #[serde(rename = "Scale")]
+    pub scale: u32, // 精度
+    #[serde(rename = "Label")]
+    pub label: String, // 地理位置的字符串信息
+    #[serde(rename = "Poiname")]
+    pub poiname: Option<String>, // 朋友圈POI的名字
+}
+
+/// 点击菜单跳转小程序事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewMiniProgramEvent {
+    #[serde(rename = "EventKey")]
+    pub event_key: String, // 跳转的小程序路径
+    #[serde(rename = "MenuId")]
+    pub menu_id: Option<String>,
+}
+
+/// 模板消息发送结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateSendJobFinishEvent {
+    #[serde(rename = "MsgID")]
+    pub msg_id: u64,
+    #[serde(rename = "Status")]
+    pub status: String, // success, failed:user block, failed: system failed
+}
+
+/// 群发结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct MassSendJobFinishEvent {
+    #[serde(rename = "MsgID")]
+    pub msg_id: u64, // 群发的消息ID
+    #[serde(rename = "Status")]
+    pub status: String, // 群发的结果，send success 或 err(num)
+    #[serde(rename = "TotalCount")]
+    pub total_count: u32, // 粉丝数
+    #[serde(rename = "FilterCount")]
+    pub filter_count: u32, // 过滤后准备发送的粉丝数
+    #[serde(rename = "SentCount")]
+    pub sent_count: u32, // 发送成功的粉丝数
+    #[serde(rename = "ErrorCount")]
+    pub error_count: u32, // 发送失败的粉丝数
+    #[serde(rename = "CopyrightCheckResult")]
+    pub copyright_check_result: Option<CopyrightCheckResult>,
+}
+
+/// 群发图文的原创校验结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct CopyrightCheckResult {
+    #[serde(rename = "Count")]
+    pub count: u32,
+    #[serde(rename = "ResultList", default, deserialize_with = "items")]
+    pub result_list: Vec<CopyrightCheckItem>,
+    #[serde(rename = "CheckState")]
+    pub check_state: u32, // 1-未被判为转载，2-被判为转载，可以群发，3-被判为转载，不能群发
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CopyrightCheckItem {
+    #[serde(rename = "ArticleIdx")]
+    pub article_idx: u32, // 群发文章的序号，从1开始
+    #[serde(rename = "UserDeclareState")]
+    pub user_declare_state: u32, // 用户声明文章的状态
+    #[serde(rename = "AuditState")]
+    pub audit_state: u32, // 系统校验的状态
+    #[serde(rename = "OriginalArticleUrl")]
+    pub original_article_url: Option<String>, // 相似原创文的url
+    #[serde(rename = "OriginalArticleType")]
+    pub original_article_type: Option<u32>, // 相似原创文的类型
+    #[serde(rename = "CanReprint")]
+    pub can_reprint: Option<u32>, // 是否能转载
+    #[serde(rename = "NeedReplaceContent")]
+    pub need_replace_content: Option<u32>, // 是否需要替换成原创文内容
+    #[serde(rename = "NeedShowReprintSource")]
+    pub need_show_reprint_source: Option<u32>, // 是否需要注明转载来源
+}
+
+/// 用户操作订阅通知弹窗事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeMsgPopupEvent {
+    #[serde(rename = "SubscribeMsgPopupEvent", default, deserialize_with = "list")]
+    pub list: Vec<SubscribeMsgPopupItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeMsgPopupItem {
+    #[serde(rename = "TemplateId")]
+    pub template_id: String,
+    #[serde(rename = "SubscribeStatusString")]
+    pub subscribe_status_string: String, // accept 或 reject
+    #[serde(rename = "PopupScene")]
+    pub popup_scene: u32, // 1-弹窗来自H5页面，2-弹窗来自图文消息
+}
+
+/// 用户管理订阅通知事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeMsgChangeEvent {
+    #[serde(rename = "SubscribeMsgChangeEvent", default, deserialize_with = "list")]
+    pub list: Vec<SubscribeMsgChangeItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeMsgChangeItem {
+    #[serde(rename = "TemplateId")]
+    pub template_id: String,
+    #[serde(rename = "SubscribeStatusString")]
+    pub subscribe_status_string: String, // reject
+}
+
+/// 发送订阅通知结果事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeMsgSentEvent {
+    #[serde(rename = "SubscribeMsgSentEvent", default, deserialize_with = "list")]
+    pub list: Vec<SubscribeMsgSentItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeMsgSentItem {
+    #[serde(rename = "TemplateId")]
+    pub template_id: String,
+    #[serde(rename = "MsgID")]
+    pub msg_id: u64,
+    #[serde(rename = "ErrorCode")]
+    pub error_code: i64, // 0 表示推送成功
+    #[serde(rename = "ErrorStatus")]
+    pub error_status: String,
+}
+
+/// 客服接入或关闭会话事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct KfSessionEvent {
+    #[serde(rename = "KfAccount")]
+    pub kf_account: String, // 完整客服账号
+}
+
+/// 客服转接会话事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct KfSwitchSessionEvent {
+    #[serde(rename = "FromKfAccount")]
+    pub from_kf_account: String,
+    #[serde(rename = "ToKfAccount")]
+    pub to_kf_account: String,
+}
+
+/// An event with an unrecognized `Event` value, kept as raw XML.
+#[derive(Debug, Clone)]
+pub struct UnknownEvent {
+    pub event: String,
+    pub raw: String,
+}
+
+/// The scene of a parametric QR code, taken from a `subscribe` or `SCAN` event.
+///
+/// A scene made only of digits that fits in a `u32` is reported as `id`, as
+/// created by `build_tmp_qr_request` with an integer scene; any other scene is
+/// reported as `str`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrScene {
+    pub id: Option<u32>,         // 场景值ID，临时二维码时为32位非0整型
+    pub str: Option<String>,     // 场景值ID（字符串形式的ID）
+    pub ticket: Option<String>,  // 二维码的ticket，可用来换取二维码图片
+    pub is_new_subscriber: bool, // 扫码后关注（subscribe）时为 true，已关注（SCAN）时为 false
+}
+
+/// `EventKey` prefix of the subscribe events sent when a user follows the
+/// account by scanning a parametric QR code.
+const QR_SCENE_PREFIX: &str = "qrscene_";
+
+/// The fields shared by every event, read before the `Event` is known.
+#[derive(Deserialize)]
+#[serde(rename = "xml")]
+struct Header {
+    #[serde(rename = "ToUserName")]
+    to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    create_time: u64,
+    #[serde(rename = "Event")]
+    event: String,
+}
+
+/// Repeated `<item>` children, as in `<PicList><item/><item/></PicList>`.
+#[derive(Deserialize)]
+struct Items<T> {
+    #[serde(rename = "item", default = "Vec::new")]
+    items: Vec<T>,
+}
+
+/// Repeated `<List>` children of the subscribe message events.
+#[derive(Deserialize)]
+struct List<T> {
+    #[serde(rename = "List", default = "Vec::new")]
+    items: Vec<T>,
+}
+
+fn items<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Items::deserialize(deserializer)?.items)
+}
+
+fn list<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(List::deserialize(deserializer)?.items)
+}
+
+impl EventMessage {
+    /// Parses the (decrypted) XML body of an event push.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::XmlDecode` if the XML is malformed or misses a
+    ///   field required by its `Event`.
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let header = from_str::<Header>(xml)?;
+
+        let event = match header.event.as_str() {
+            EventType::SUBSCRIBE => Event::Subscribe(from_str(xml)?),
+            EventType::UNSUBSCRIBE => Event::Unsubscribe,
+            EventType::SCAN => Event::Scan(from_str(xml)?),
+            EventType::LOCATION => Event::Location(from_str(xml)?),
+            EventType::CLICK => Event::Click(from_str(xml)?),
+            EventType::VIEW => Event::View(from_str(xml)?),
+            EventType::SCANCODE_PUSH => Event::ScanCodePush(from_str(xml)?),
+            EventType::SCANCODE_WAITMSG => Event::ScanCodeWaitMsg(from_str(xml)?),
+            EventType::PIC_SYSPHOTO => Event::PicSysPhoto(from_str(xml)?),
+            EventType::PIC_PHOTO_OR_ALBUM => Event::PicPhotoOrAlbum(from_str(xml)?),
+            EventType::PIC_WEIXIN => Event::PicWeixin(from_str(xml)?),
+            EventType::LOCATION_SELECT => Event::LocationSelect(from_str(xml)?),
+            EventType::VIEW_MINIPROGRAM => Event::ViewMiniProgram(from_str(xml)?),
+            EventType::TEMPLATE_SEND_JOB_FINISH => Event::TemplateSendJobFinish(from_str(xml)?),
+            EventType::MASS_SEND_JOB_FINISH => Event::MassSendJobFinish(from_str(xml)?),
+            EventType::SUBSCRIBE_MSG_POPUP => Event::SubscribeMsgPopup(from_str(xml)?),
+            EventType::SUBSCRIBE_MSG_CHANGE => Event::SubscribeMsgChange(from_str(xml)?),
+            EventType::SUBSCRIBE_MSG_SENT => Event::SubscribeMsgSent(from_str(xml)?),
+            EventType::KF_CREATE_SESSION => Event::KfCreateSession(from_str(xml)?),
+            EventType::KF_CLOSE_SESSION => Event::KfCloseSession(from_str(xml)?),
+            EventType::KF_SWITCH_SESSION => Event::KfSwitchSession(from_str(xml)?),
+            _ => Event::Unknown(UnknownEvent {
+                event: header.event,
+                raw: xml.to_string(),
+            }),
+        };
+
+        Ok(EventMessage {
+            to_user_name: header.to_user_name,
+            from_user_name: header.from_user_name,
+            create_time: header.create_time,
+            event,
+        })
+    }
+}
+
+impl Event {
+    /// Returns the `Event` value of the push, e.g. `subscribe` or `CLICK`.
+    pub fn name(&self) -> &str {
+        match self {
+            Event::Subscribe(_) => EventType::SUBSCRIBE,
+            Event::Unsubscribe => EventType::UNSUBSCRIBE,
+            Event::Scan(_) => EventType::SCAN,
+            Event::Location(_) => EventType::LOCATION,
+            Event::Click(_) => EventType::CLICK,
+            Event::View(_) => EventType::VIEW,
+            Event::ScanCodePush(_) => EventType::SCANCODE_PUSH,
+            Event::ScanCodeWaitMsg(_) => EventType::SCANCODE_WAITMSG,
+            Event::PicSysPhoto(_) => EventType::PIC_SYSPHOTO,
+            Event::PicPhotoOrAlbum(_) => EventType::PIC_PHOTO_OR_ALBUM,
+            Event::PicWeixin(_) => EventType::PIC_WEIXIN,
+            Event::LocationSelect(_) => EventType::LOCATION_SELECT,
+            Event::ViewMiniProgram(_) => EventType::VIEW_MINIPROGRAM,
+            Event::TemplateSendJobFinish(_) => EventType::TEMPLATE_SEND_JOB_FINISH,
+            Event::MassSendJobFinish(_) => EventType::MASS_SEND_JOB_FINISH,
+            Event::SubscribeMsgPopup(_) => EventType::SUBSCRIBE_MSG_POPUP,
+            Event::SubscribeMsgChange(_) => EventType::SUBSCRIBE_MSG_CHANGE,
+            Event::SubscribeMsgSent(_) => EventType::SUBSCRIBE_MSG_SENT,
+            Event::KfCreateSession(_) => EventType::KF_CREATE_SESSION,
+            Event::KfCloseSession(_) => EventType::KF_CLOSE_SESSION,
+            Event::KfSwitchSession(_) => EventType::KF_SWITCH_SESSION,
+            Event::Unknown(unknown) => &unknown.event,
+        }
+    }
+
+    /// Returns the scene of the parametric QR code the user scanned, stripping
+    /// the `qrscene_` prefix of `subscribe` events.
+    ///
+    /// Returns `None` for other events and for a `subscribe` that did not come
+    /// from a QR code.
+    pub fn qr_scene(&self) -> Option<QrScene> {
+        let (scene, ticket, is_new_subscriber) = match self {
+            Event::Subscribe(e) => {
+                let scene = e.event_key.as_deref()?.strip_prefix(QR_SCENE_PREFIX)?;
+                (scene, &e.ticket, true)
+            }
+            Event::Scan(e) => (e.event_key.as_str(), &e.ticket, false),
+            _ => return None,
+        };
+
+        if scene.is_empty() {
+            return None;
+        }
+
+        let id = scene.parse::<u32>().ok();
+        Some(QrScene {
+            id,
+            str: id.is_none().then(|| scene.to_string()),
+            ticket: ticket.clone(),
+            is_new_subscriber,
+        })
+    }
+
+    /// Returns the `EventKey` of the push, if the event carries one.
+    pub fn event_key(&self) -> Option<&str> {
+        match self {
+            Event::Subscribe(e) => e.event_key.as_deref(),
+            Event::Scan(e) => Some(&e.event_key),
+            Event::Click(e) => Some(&e.event_key),
+            Event::View(e) => Some(&e.event_key),
+            Event::ScanCodePush(e) | Event::ScanCodeWaitMsg(e) => Some(&e.event_key),
+            Event::PicSysPhoto(e) | Event::PicPhotoOrAlbum(e) | Event::PicWeixin(e) => {
+                Some(&e.event_key)
+            }
+            Event::LocationSelect(e) => Some(&e.event_key),
+            Event::ViewMiniProgram(e) => Some(&e.event_key),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use quick_xml::de::from_str;
+use serde::{Deserialize, Serialize};
+
+use super::event::EventMessage;
+use super::message::MsgType;
+use crate::Result;
+
+#[cfg(test)]
+mod tests {
+    use super::InboundMessage;
+    use crate::official_account::event::Event;
+
+    #[test]
+    fn parse_text() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1348831860</CreateTime><MsgType><![CDATA[text]]></MsgType><Content><![CDATA[this is a test]]></Content><MsgId>1234567890123456</MsgId></xml>";
+        let message = InboundMessage::from_xml(xml).unwrap();
+
+        let InboundMessage::Text(text) = &message else {
+            panic!("expected a text message, got {:?}", message);
+        };
+        assert_eq!(text.content, "this is a test");
+        assert_eq!(message.msg_type(), "text");
+        assert_eq!(message.msg_id(), Some(1234567890123456));
+        assert_eq!(message.from_user_name(), "oUser");
+        assert_eq!(message.to_user_name(), "gh_123456789abc");
+        assert_eq!(message.create_time(), 1348831860);
+    }
+
+    #[test]
+    fn parse_image() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1348831860</CreateTime><MsgType><![CDATA[image]]></MsgType><PicUrl><![CDATA[http://example.com/pic.jpg]]></PicUrl><MediaId><![CDATA[media_id]]></MediaId><MsgId>1234567890123456</MsgId></xml>";
+
+        let InboundMessage::Image(image) = InboundMessage::from_xml(xml).unwrap() else {
+            panic!("expected an image message");
+        };
+        assert_eq!(image.pic_url, "http://example.com/pic.jpg");
+        assert_eq!(image.media_id, "media_id");
+    }
+
+    #[test]
+    fn parse_voice() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1357290913</CreateTime><MsgType><![CDATA[voice]]></MsgType><MediaId><![CDATA[media_id]]></MediaId><Format><![CDATA[amr]]></Format><Recognition><![CDATA[腾讯微信团队]]></Recognition><MsgId>1234567890123456</MsgId></xml>";
+
+        let InboundMessage::Voice(voice) = InboundMessage::from_xml(xml).unwrap() else {
+            panic!("expected a voice message");
+        };
+        assert_eq!(voice.format, "amr");
+        assert_eq!(voice.recognition.as_deref(), Some("腾讯微信团队"));
+    }
+
+    #[test]
+    fn parse_short_video() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1357290913</CreateTime><MsgType><![CDATA[shortvideo]]></MsgType><MediaId><![CDATA[media_id]]></MediaId><ThumbMediaId><![CDATA[thumb_media_id]]></ThumbMediaId><MsgId>1234567890123456</MsgId></xml>";
+
+        let InboundMessage::ShortVideo(video) = InboundMessage::from_xml(xml).unwrap() else {
+            panic!("expected a short video message");
+        };
+        assert_eq!(video.thumb_media_id, "thumb_media_id");
+    }
+
+    #[test]
+    fn parse_location() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1351776360</CreateTime><MsgType><![CDATA[location]]></MsgType><Location_X>23.134521</Location_X><Location_Y>113.358803</Location_Y><Scale>20</Scale><Label><![CDATA[位置信息]]></Label><MsgId>1234567890123456</MsgId></xml>";
+
+        let InboundMessage::Location(location) = InboundMessage::from_xml(xml).unwrap() else {
+            panic!("expected a location message");
+        };
+        assert_eq!(location.location_x, 23.134521);
+        assert_eq!(location.location_y, 113.358803);
+        assert_eq!(location.scale, 20);
+        assert_eq!(location.label, "位置信息");
+    }
+
+    #[test]
+    fn parse_link() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1351776360</CreateTime><MsgType><![CDATA[link]]></MsgType><Title><![CDATA[公众平台官网链接]]></Title><Description><![CDATA[公众平台官网链接]]></Description><Url><![CDATA[https://mp.weixin.qq.com]]></Url><MsgId>1234567890123456</MsgId></xml>";
+
+        let InboundMessage::Link(link) = InboundMessage::from_xml(xml).unwrap() else {
+            panic!("expected a link message");
+        };
+        assert_eq!(link.url, "https://mp.weixin.qq.com");
+    }
+
+    #[test]
+    fn parse_event() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>123456789</CreateTime><MsgType><![CDATA[event]]></MsgType><Event><![CDATA[CLICK]]></Event><EventKey><![CDATA[V1001_TODAY_MUSIC]]></EventKey></xml>";
+        let message = InboundMessage::from_xml(xml).unwrap();
+
+        let InboundMessage::Event(event) = &message else {
+            panic!("expected an event");
+        };
+        assert!(matches!(event.event, Event::Click(_)));
+        assert_eq!(event.event.event_key(), Some("V1001_TODAY_MUSIC"));
+        assert_eq!(message.from_user_name(), "oUser");
+        assert_eq!(message.msg_id(), None);
+    }
+
+    #[test]
+    fn parse_unknown() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1351776360</CreateTime><MsgType><![CDATA[hologram]]></MsgType><Depth>3</Depth></xml>";
+        let message = InboundMessage::from_xml(xml).unwrap();
+
+        let InboundMessage::Unknown(unknown) = &message else {
+            panic!("expected an unknown message");
+        };
+        assert_eq!(unknown.msg_type, "hologram");
+        assert_eq!(unknown.raw, xml);
+        assert_eq!(message.from_user_name(), "oUser");
+    }
+
+    #[test]
+    fn reject_missing_fields() {
+        let xml = "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1351776360</CreateTime><MsgType><![CDATA[image]]></MsgType></xml>";
+        assert!(InboundMessage::from_xml(xml).is_err());
+    }
+}
+
+/// A message or event pushed to the callback URL.
+///
+/// [接收普通消息](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Receiving_standard_messages.html)
+#[derive(Debug, Clone)]
+pub enum InboundMessage {
+    Text(TextMessage),
+    Image(ImageMessage),
+    Voice(VoiceMessage),
+    Video(VideoMessage),
+    ShortVideo(VideoMessage),
+    Location(LocationMessage),
+    Link(LinkMessage),
+    MiniProgramPage(MiniProgramPageMessage),
+    Event(EventMessage),
+    /// A `MsgType` this SDK does not know yet.
+    Unknown(UnknownMessage),
+}
+
+/// 文本消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "xml")]
+pub struct TextMessage {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: String, // 微信号(公众号原始id)
+    #[serde(rename = "FromUserName")]
+    pub from_user_name: String, // 发送方账号（一个OpenID）
+    #[serde(rename = "CreateTime")]
+    pub create_time: u64, // 消息创建时间 （整型）
+    #[serde(rename = "Content")]
+    pub content: String, // 文本消息内容
+    #[serde(rename = "MsgId")]
+    pub msg_id: u64, // 消息id，64位整型
+    #[serde(rename = "MsgDataId")]
+    pub msg_data_id: Option<u64>, // 消息的数据ID（消息如果来自文章时才有）
+    #[serde(rename = "Idx")]
+    pub idx: Option<u64>, // 多图文时第几篇文章，从1开始（消息如果来自文章时才有）
+}
+
+/// 图片消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "xml")]
+pub struct ImageMessage {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    pub from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    pub create_time: u64,
+    #[serde(rename = "PicUrl")]
+    pub pic_url: String, // 图片链接（由系统生成）
+    #[serde(rename = "MediaId")]
+    pub media_id: String, // 图片消息媒体id，可以调用获取临时素材接口拉取数据
+    #[serde(rename = "MsgId")]
+    pub msg_id: u64,
+    #[serde(rename = "MsgDataId")]
+    pub msg_data_id: Option<u64>,
+    #[serde(rename = "Idx")]
+    pub idx: Option<u64>,
+}
+
+/// 语音消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "xml")]
+pub struct VoiceMessage {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    pub from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    pub create_time: u64,
+    #[serde(rename = "MediaId")]
+    pub media_id: String, // 语音消息媒体id
+    #[serde(rename = "Format")]
+    pub format: String, // 语音格式，如amr，speex等
+    #[serde(rename = "Recognition")]
+    pub recognition: Option<String>, // 语音识别结果，UTF8编码
+    #[serde(rename = "MediaId16K")]
+    pub media_id_16k: Option<String>, // 16K采样率语音消息媒体id
+    #[serde(rename = "MsgId")]
+    pub msg_id: u64,
+    #[serde(rename = "MsgDataId")]
+    pub msg_data_id: Option<u64>,
+    #[serde(rename = "Idx")]
+    pub idx: Option<u64>,
+}
+
+/// 视频消息或小视频消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "xml")]
+pub struct VideoMessage {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    pub from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    pub create_time: u64,
+    #[serde(rename = "MediaId")]
+    pub media_id: String, // 视频消息媒体id
+    #[serde(rename = "ThumbMediaId")]
+    pub thumb_media_id: String, // 视频消息缩略图的媒体id
+    #[serde(rename = "MsgId")]
+    pub msg_id: u64,
+    #[serde(rename = "MsgDataId")]
+    pub msg_data_id: Option<u64>,
+    #[serde(rename = "Idx")]
+    pub idx: Option<u64>,
+}
+
+/// 地理位置消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "xml")]
+pub struct LocationMessage {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    pub from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    pub create_time: u64,
+    #[serde(rename = "Location_X")]
+    pub location_x: f64, // 地理位置纬度
+    #[serde(rename = "Location_Y")]
+    pub location_y: f64, // 地理位置经度
+    #[serde(rename = "Scale")]
+    pub scale: u32, // 地图缩放大小
+    #[serde(rename = "Label")]
+    pub label: String, // 地理位置信息
+    #[serde(rename = "MsgId")]
+    pub msg_id: u64,
+    #[serde(rename = "MsgDataId")]
+    pub msg_data_id: Option<u64>,
+    #[serde(rename = "Idx")]
+    pub idx: Option<u64>,
+}
+
+/// 链接消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "xml")]
+pub struct LinkMessage {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    pub from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    pub create_time: u64,
+    #[serde(rename = "Title")]
+    pub title: String, // 消息标题
+    #[serde(rename = "Description")]
+    pub description: String, // 消息描述
+    #[serde(rename = "Url")]
+    pub url: String, // 消息链接
+    #[serde(rename = "MsgId")]
+    pub msg_id: u64,
+    #[serde(rename = "MsgDataId")]
+    pub msg_data_id: Option<u64>,
+    #[serde(rename = "Idx")]
+    pub idx: Option<u64>,
+}
+
+/// 小程序卡片消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "xml")]
+pub struct MiniProgramPageMessage {
+    #[serde(rename = "ToUserName")]
+    pub to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    pub from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    pub create_time: u64,
+    #[serde(rename = "Title")]
+    pub title: String, // 小程序卡片标题
+    #[serde(rename = "AppId")]
+    pub app_id: String, // 小程序的appid
+    #[serde(rename = "PagePath")]
+    pub page_path: String, // 小程序的页面路径
+    #[serde(rename = "ThumbUrl")]
+    pub thumb_url: Option<String>, // 封面图片的临时cdn链接
+    #[serde(rename = "ThumbMediaId")]
+    pub thumb_media_id: Option<String>, // 封面图片的临时素材id
+    #[serde(rename = "MsgId")]
+    pub msg_id: u64,
+}
+
+/// A message with an unrecognized `MsgType`, kept as raw XML.
+#[derive(Debug, Clone)]
+pub struct UnknownMessage {
+    pub to_user_name: String,
+    pub from_user_name: String,
+    pub create_time: u64,
+    pub msg_type: String,
+    pub raw: String,
+}
+
+/// The fields shared by every message, read before the `MsgType` is known.
+#[derive(Deserialize)]
+#[serde(rename = "xml")]
+struct Header {
+    #[serde(rename = "ToUserName")]
+    to_user_name: String,
+    #[serde(rename = "FromUserName")]
+    from_user_name: String,
+    #[serde(rename = "CreateTime")]
+    create_time: u64,
+    #[serde(rename = "MsgType")]
+    msg_type: String,
+}
+
+impl InboundMessage {
+    /// Parses the (decrypted) XML body of a callback request.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::XmlDecode` if the XML is malformed or misses a
+    ///   field required by its `MsgType`.
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let header = from_str::<Header>(xml)?;
+
+        let message = match header.msg_type.as_str() {
+            MsgType::TEXT => InboundMessage::Text(from_str(xml)?),
+            MsgType::IMAGE => InboundMessage::Image(from_str(xml)?),
+            MsgType::VOICE => InboundMessage::Voice(from_str(xml)?),
+            MsgType::VIDEO => InboundMessage::Video(from_str(xml)?),
+            MsgType::SHORTVIDEO => InboundMessage::ShortVideo(from_str(xml)?),
+            MsgType::LOCATION => InboundMessage::Location(from_str(xml)?),
+            MsgType::LINK => InboundMessage::Link(from_str(xml)?),
+            MsgType::MINIPROGRAMPAGE => InboundMessage::MiniProgramPage(from_str(xml)?),
+            MsgType::EVENT => InboundMessage::Event(EventMessage::from_xml(xml)?),
+            _ => InboundMessage::Unknown(UnknownMessage {
+                to_user_name: header.to_user_name,
+                from_user_name: header.from_user_name,
+                create_time: header.create_time,
+                msg_type: header.msg_type,
+                raw: xml.to_string(),
+            }),
+        };
+
+        Ok(message)
+    }
+
+    /// Returns the `MsgType` of the message.
+    pub fn msg_type(&self) -> &str {
+        match self {
+            InboundMessage::Text(_) => MsgType::TEXT,
+            InboundMessage::Image(_) => MsgType::IMAGE,
+            InboundMessage::Voice(_) => MsgType::VOICE,
+            InboundMessage::Video(_) => MsgType::VIDEO,
+            InboundMessage::ShortVideo(_) => MsgType::SHORTVIDEO,
+            InboundMessage::Location(_) => MsgType::LOCATION,
+            InboundMessage::Link(_) => MsgType::LINK,
+            InboundMessage::MiniProgramPage(_) => MsgType::MINIPROGRAMPAGE,
+            InboundMessage::Event(_) => MsgType::EVENT,
+            InboundMessage::Unknown(m) => &m.msg_type,
+        }
+    }
+
+    /// Returns the original id (`gh_...`) of the account the message was sent to.
+    pub fn to_user_name(&self) -> &str {
+        self.header().0
+    }
+
+    /// Returns the openid of the sender.
+    pub fn from_user_name(&self) -> &str {
+        self.header().1
+    }
+
+    /// Returns the creation time of the message, in seconds since the epoch.
+    pub fn create_time(&self) -> u64 {
+        self.header().2
+    }
+
+    /// Returns the `MsgId`; events and unknown messages have none.
+    pub fn msg_id(&self) -> Option<u64> {
+        match self {
+            InboundMessage::Text(m) => Some(m.msg_id),
+            InboundMessage::Image(m) => Some(m.msg_id),
+            InboundMessage::Voice(m) => Some(m.msg_id),
+            InboundMessage::Video(m) | InboundMessage::ShortVideo(m) => Some(m.msg_id),
+            InboundMessage::Location(m) => Some(m.msg_id),
+            InboundMessage::Link(m) => Some(m.msg_id),
+            InboundMessage::MiniProgramPage(m) => Some(m.msg_id),
+            InboundMessage::Event(_) | InboundMessage::Unknown(_) => None,
+        }
+    }
+
+    pub fn get_open_id(&self) -> String {
+        self.from_user_name().to_string()
+    }
+
+    pub fn get_gh_id(&self) -> String {
+        self.to_user_name().to_string()
+    }
+
+    fn header(&self) -> (&str, &str, u64) {
+        match self {
+            InboundMessage::Text(m) => (&m.to_user_name, &m.from_user_name, m.create_time),
+            InboundMessage::Image(m) => (&m.to_user_name, &m.from_user_name, m.create_time),
+            InboundMessage::Voice(m) => (&m.to_user_name, &m.from_user_name, m.create_time),
+            InboundMessage::Video(m) | InboundMessage::ShortVideo(m) => {
+                (&m.to_user_name, &m.from_user_name, m.create_time)
+            }
+            InboundMessage::Location(m) => (&m.to_user_name, &m.from_user_name, m.create_time),
+            InboundMessage::Link(m) => (&m.to_user_name, &m.from_user_name, m.create_time),
+            InboundMessage::MiniProgramPage(m) => {
+                (&m.to_user_name, &m.from_user_name, m.create_time)
+            }
+            InboundMessage::Event(m) => (&m.to_user_name, &m.from_user_name, m.create_time),
+            InboundMessage::Unknown(m) => (&m.to_user_name, &m.from_user_name, m.create_time),
+        }
+    }
+}
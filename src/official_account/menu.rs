@@ -1,17 +1,240 @@
-use crate::OfficialAccount;
+use serde::{Deserialize, Deserializer, Serialize};
 
-use super::core::BasicResponse;
+use crate::{OfficialAccount, Result, WechatError};
 
-pub(crate) const DELETE_MENU_URL: &str = "https://api.weixin.qq.com/cgi-bin/menu/delete?access_token=";
+use super::{core::BasicResponse, response};
+
+pub(crate) const DELETE_MENU_PATH: &str = "/cgi-bin/menu/delete?access_token=";
+pub(crate) const CREATE_MENU_PATH: &str = "/cgi-bin/menu/create?access_token=";
+pub(crate) const GET_MENU_PATH: &str = "/cgi-bin/menu/get?access_token=";
+pub(crate) const ADD_CONDITIONAL_MENU_PATH: &str = "/cgi-bin/menu/addconditional?access_token=";
+pub(crate) const DEL_CONDITIONAL_MENU_PATH: &str = "/cgi-bin/menu/delconditional?access_token=";
+pub(crate) const TRY_MATCH_MENU_PATH: &str = "/cgi-bin/menu/trymatch?access_token=";
+pub(crate) const SELFMENU_INFO_PATH: &str = "/cgi-bin/get_current_selfmenu_info?access_token=";
 
 #[cfg(test)]
 mod tests {
-    use std::env;
+    use super::{ActionButton, Button, ConditionalMenu, MatchRule, Menu, SubMenu};
+    use crate::WechatError;
+    use crate::test_util::MockServer;
+
+    const MENU_JSON: &str = r#"{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC"},{"name":"菜单","sub_button":[{"type":"view","name":"搜索","url":"http://www.soso.com/"},{"type":"miniprogram","name":"wxa","url":"http://mp.weixin.qq.com","appid":"wx286b93c14bbf93aa","pagepath":"pages/lunar/index"},{"type":"click","name":"赞一下我们","key":"V1001_GOOD"}]}]}"#;
+
+    fn menu() -> Menu {
+        Menu {
+            button: vec![
+                ActionButton::Click {
+                    name: "今日歌曲".to_string(),
+                    key: "V1001_TODAY_MUSIC".to_string(),
+                }
+                .into(),
+                SubMenu {
+                    name: "菜单".to_string(),
+                    sub_button: vec![
+                        ActionButton::View {
+                            name: "搜索".to_string(),
+                            url: "http://www.soso.com/".to_string(),
+                        },
+                        ActionButton::Miniprogram {
+                            name: "wxa".to_string(),
+                            url: "http://mp.weixin.qq.com".to_string(),
+                            appid: "wx286b93c14bbf93aa".to_string(),
+                            pagepath: "pages/lunar/index".to_string(),
+                        },
+                        ActionButton::Click {
+                            name: "赞一下我们".to_string(),
+                            key: "V1001_GOOD".to_string(),
+                        },
+                    ],
+                }
+                .into(),
+            ],
+            menuid: None,
+        }
+    }
+
+    #[test]
+    fn serialize_menu() {
+        assert_eq!(serde_json::to_string(&menu()).unwrap(), MENU_JSON);
+        assert_eq!(serde_json::from_str::<Menu>(MENU_JSON).unwrap(), menu());
+    }
+
+    #[test]
+    fn deserialize_get_menu_response() {
+        let body = r#"{"menu":{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC","sub_button":[]},{"type":"article_view_limited","name":"文章","article_id":"ARTICLE_ID","sub_button":[]},{"name":"菜单","sub_button":[{"type":"scancode_waitmsg","name":"扫码","key":"rselfmenu_0_0","sub_button":[]}]}],"menuid":208396938}}"#;
+        let info = super::response::decode_str::<super::MenuInfo>(body).unwrap();
+
+        assert_eq!(info.menu.menuid, Some(208396938));
+        assert!(matches!(
+            &info.menu.button[1],
+            Button::Action(ActionButton::ArticleViewLimited { article_id, .. }) if article_id == "ARTICLE_ID"
+        ));
+        let Button::Menu(sub_menu) = &info.menu.button[2] else {
+            panic!("expected a sub menu");
+        };
+        assert_eq!(sub_menu.sub_button[0].name(), "扫码");
+    }
+
+    #[test]
+    fn deserialize_unknown_button() {
+        let body = r#"{"menu":{"button":[{"type":"view_limited","name":"图文","media_id":"MEDIA_ID","sub_button":[]},{"name":"菜单","sub_button":[{"type":"view_limited","name":"图文","media_id":"MEDIA_ID","sub_button":[]}]}]}}"#;
+        let info = super::response::decode_str::<super::MenuInfo>(body).unwrap();
+
+        let Button::Unknown(button) = &info.menu.button[0] else {
+            panic!("expected an unknown button");
+        };
+        assert_eq!(button["type"], "view_limited");
+        assert!(matches!(&info.menu.button[1], Button::Unknown(_)));
+        assert!(info.menu.validate().is_ok());
+        assert_eq!(
+            serde_json::to_string(&info.menu.button[0]).unwrap(),
+            r#"{"media_id":"MEDIA_ID","name":"图文","sub_button":[],"type":"view_limited"}"#
+        );
+
+        assert!(serde_json::from_str::<Button>(r#"{"type":"click","name":"歌曲"}"#).is_err());
+    }
+
+    #[test]
+    fn validate_menu() {
+        assert!(menu().validate().is_ok());
+
+        let mut too_many = menu();
+        too_many.button = vec![too_many.button[0].clone(); 4];
+        assert!(matches!(
+            too_many.validate(),
+            Err(WechatError::InvalidArgument(_))
+        ));
+
+        let mut long_name = menu();
+        long_name.button[0] = ActionButton::Click {
+            name: "一二三四五六".to_string(),
+            key: "KEY".to_string(),
+        }
+        .into();
+        assert!(long_name.validate().is_err());
+
+        let mut too_many_sub = menu();
+        if let Button::Menu(sub_menu) = &mut too_many_sub.button[1] {
+            sub_menu.sub_button = vec![sub_menu.sub_button[0].clone(); 6];
+        }
+        assert!(too_many_sub.validate().is_err());
+
+        let mut empty_key = menu();
+        empty_key.button[0] = ActionButton::Click {
+            name: "歌曲".to_string(),
+            key: String::new(),
+        }
+        .into();
+        assert!(empty_key.validate().is_err());
+    }
+
+    #[test]
+    fn deserialize_conditional_menus() {
+        let body = r#"{"menu":{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC","sub_button":[]}],"menuid":208396938},"conditionalmenu":[{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC","sub_button":[]}],"matchrule":{"group_id":2,"sex":1,"country":"中国","province":"广东","city":"广州","client_platform_type":2},"menuid":208396993}]}"#;
+        let info = super::response::decode_str::<super::MenuInfo>(body).unwrap();
+        let matchrule = &info.conditionalmenu[0].matchrule;
+        assert_eq!(matchrule.sex.as_deref(), Some("1"));
+        assert_eq!(matchrule.client_platform_type.as_deref(), Some("2"));
+
+        let body = r#"{"menu":{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC","sub_button":[]}],"menuid":208396938},"conditionalmenu":[{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC","sub_button":[]}],"matchrule":{"tag_id":"2","client_platform_type":"2"},"menuid":208396993}]}"#;
+        let info = super::response::decode_str::<super::MenuInfo>(body).unwrap();
+        assert_eq!(info.conditionalmenu.len(), 1);
+        assert_eq!(info.conditionalmenu[0].menuid, Some(208396993));
+        assert_eq!(
+            info.conditionalmenu[0].matchrule.tag_id.as_deref(),
+            Some("2")
+        );
+    }
+
+    #[tokio::test]
+    async fn conditional_menus() {
+        let server = MockServer::with_token(|req| {
+            if req.target.starts_with("/cgi-bin/menu/addconditional") {
+                r#"{"menuid":"208379533"}"#.to_string()
+            } else if req.target.starts_with("/cgi-bin/menu/trymatch") {
+                r#"{"button":[{"type":"view","name":"tx","url":"http://www.qq.com/","sub_button":[]}]}"#
+                    .to_string()
+            } else {
+                r#"{"errcode":0,"errmsg":"ok"}"#.to_string()
+            }
+        })
+        .await;
+        let account = server.account();
+
+        let conditional = ConditionalMenu {
+            button: menu().button,
+            matchrule: MatchRule {
+                tag_id: Some("2".to_string()),
+                language: Some("zh_CN".to_string()),
+                ..Default::default()
+            },
+            menuid: None,
+        };
+        assert_eq!(
+            account.add_conditional_menu(&conditional).await.unwrap(),
+            208379533
+        );
+
+        let empty_rule = ConditionalMenu {
+            matchrule: MatchRule::default(),
+            ..conditional
+        };
+        assert!(matches!(
+            account.add_conditional_menu(&empty_rule).await,
+            Err(WechatError::InvalidArgument(_))
+        ));
+
+        account.del_conditional_menu(208379533).await.unwrap();
+
+        let matched = account.try_match_menu("weixin").await.unwrap();
+        assert_eq!(matched.button.len(), 1);
 
-    use crate::{Config, OfficialAccount};
+        let requests = server.requests();
+        let body = |prefix: &str| {
+            requests
+                .iter()
+                .find(|req| req.target.starts_with(prefix))
+                .map(|req| req.body.clone())
+                .unwrap()
+        };
+        assert!(
+            body("/cgi-bin/menu/addconditional")
+                .ends_with(r#""matchrule":{"tag_id":"2","language":"zh_CN"}}"#)
+        );
+        assert_eq!(
+            body("/cgi-bin/menu/delconditional"),
+            r#"{"menuid":"208379533"}"#
+        );
+        assert_eq!(body("/cgi-bin/menu/trymatch"), r#"{"user_id":"weixin"}"#);
+    }
+
+    #[tokio::test]
+    async fn create_menu() {
+        let server = MockServer::with_token(|_| r#"{"errcode":0,"errmsg":"ok"}"#.to_string()).await;
+        let account = server.account();
+
+        account.create_menu(&menu()).await.unwrap();
+
+        let mut too_many = menu();
+        too_many.button = vec![too_many.button[0].clone(); 4];
+        assert!(account.create_menu(&too_many).await.is_err());
 
+        let requests = server.requests();
+        let creates: Vec<_> = requests
+            .iter()
+            .filter(|req| req.target.starts_with("/cgi-bin/menu/create"))
+            .collect();
+        assert_eq!(creates.len(), 1);
+        assert_eq!(creates[0].body, MENU_JSON);
+    }
+
+    #[cfg(feature = "redis")]
     #[tokio::test]
     async fn delete_menu() {
+        use std::env;
+
+        use crate::{Config, OfficialAccount};
+
         dotenv::dotenv().ok();
 
         let appid = env::var("APPID").expect("APPID not set");
@@ -30,28 +253,570 @@ mod tests {
     }
 }
 
+/// Maximum number of top-level buttons.
+pub const MAX_BUTTONS: usize = 3;
+/// Maximum number of buttons in a sub menu.
+pub const MAX_SUB_BUTTONS: usize = 5;
+/// Maximum length of a top-level button name, in bytes.
+pub const MAX_BUTTON_NAME_BYTES: usize = 16;
+/// Maximum length of a sub button name, in bytes.
+pub const MAX_SUB_BUTTON_NAME_BYTES: usize = 60;
+/// Maximum length of a `key`, in bytes.
+pub const MAX_KEY_BYTES: usize = 128;
+/// Maximum length of a `url`, in bytes.
+pub const MAX_URL_BYTES: usize = 1024;
+
+/// A custom menu, as created by `create_menu`.
+///
+/// [自定义菜单](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Creating_Custom-Defined_Menu.html)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Menu {
+    pub button: Vec<Button>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub menuid: Option<u64>, // 仅查询时返回，有个性化菜单时存在
+}
+
+/// A top-level button: either an action, or a sub menu of up to five actions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Button {
+    Action(ActionButton),
+    Menu(SubMenu),
+    /// A button, or a sub menu holding a button, whose `type` is not modelled,
+    /// such as the legacy `view_limited`. It is kept as WeChat returned it and
+    /// left to WeChat to validate.
+    Unknown(serde_json::Value),
+}
+
+/// A button that opens a sub menu.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubMenu {
+    pub name: String,
+    pub sub_button: Vec<ActionButton>,
+}
+
+/// A button that triggers an action, tagged by its `type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActionButton {
+    /// 点击推事件
+    Click { name: String, key: String },
+    /// 跳转URL
+    View { name: String, url: String },
+    /// 跳转小程序，`url` 为不支持小程序的老版本客户端打开的网页
+    Miniprogram {
+        name: String,
+        url: String,
+        appid: String,
+        pagepath: String,
+    },
+    /// 扫码推事件
+    ScancodePush { name: String, key: String },
+    /// 扫码推事件且弹出"消息接收中"提示框
+    ScancodeWaitmsg { name: String, key: String },
+    /// 弹出系统拍照发图
+    PicSysphoto { name: String, key: String },
+    /// 弹出拍照或者相册发图
+    PicPhotoOrAlbum { name: String, key: String },
+    /// 弹出微信相册发图器
+    PicWeixin { name: String, key: String },
+    /// 弹出地理位置选择器
+    LocationSelect { name: String, key: String },
+    /// 下发消息（除文本消息）
+    MediaId { name: String, media_id: String },
+    /// 下发发布后的图文消息
+    ArticleId { name: String, article_id: String },
+    /// 跳转发布后的图文消息URL
+    ArticleViewLimited { name: String, article_id: String },
+}
+
+impl<'de> Deserialize<'de> for Button {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+
+        let is_modelled = |button: &serde_json::Value| {
+            button
+                .get("type")
+                .and_then(serde_json::Value::as_str)
+                .is_none_or(|kind| ActionButton::TYPES.contains(&kind))
+        };
+        let sub_buttons = value
+            .get("sub_button")
+            .and_then(serde_json::Value::as_array);
+        if !is_modelled(&value)
+            || !sub_buttons.is_none_or(|buttons| buttons.iter().all(is_modelled))
+        {
+            return Ok(Button::Unknown(value));
+        }
+
+        if value.get("type").is_some() {
+            ActionButton::deserialize(value).map(Button::Action)
+        } else {
+            SubMenu::deserialize(value).map(Button::Menu)
+        }
+        .map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<ActionButton> for Button {
+    fn from(button: ActionButton) -> Self {
+        Button::Action(button)
+    }
+}
+
+impl From<SubMenu> for Button {
+    fn from(menu: SubMenu) -> Self {
+        Button::Menu(menu)
+    }
+}
+
+impl ActionButton {
+    /// The `type` of every variant.
+    const TYPES: [&'static str; 12] = [
+        "click",
+        "view",
+        "miniprogram",
+        "scancode_push",
+        "scancode_waitmsg",
+        "pic_sysphoto",
+        "pic_photo_or_album",
+        "pic_weixin",
+        "location_select",
+        "media_id",
+        "article_id",
+        "article_view_limited",
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            ActionButton::Click { name, .. }
+            | ActionButton::View { name, .. }
+            | ActionButton::Miniprogram { name, .. }
+            | ActionButton::ScancodePush { name, .. }
+            | ActionButton::ScancodeWaitmsg { name, .. }
+            | ActionButton::PicSysphoto { name, .. }
+            | ActionButton::PicPhotoOrAlbum { name, .. }
+            | ActionButton::PicWeixin { name, .. }
+            | ActionButton::LocationSelect { name, .. }
+            | ActionButton::MediaId { name, .. }
+            | ActionButton::ArticleId { name, .. }
+            | ActionButton::ArticleViewLimited { name, .. } => name,
+        }
+    }
+
+    fn validate(&self, max_name_bytes: usize) -> Result<()> {
+        check_len("button name", self.name(), max_name_bytes)?;
+
+        match self {
+            ActionButton::Click { key, .. }
+            | ActionButton::ScancodePush { key, .. }
+            | ActionButton::ScancodeWaitmsg { key, .. }
+            | ActionButton::PicSysphoto { key, .. }
+            | ActionButton::PicPhotoOrAlbum { key, .. }
+            | ActionButton::PicWeixin { key, .. }
+            | ActionButton::LocationSelect { key, .. } => check_len("key", key, MAX_KEY_BYTES),
+            ActionButton::View { url, .. } => check_len("url", url, MAX_URL_BYTES),
+            ActionButton::Miniprogram {
+                url,
+                appid,
+                pagepath,
+                ..
+            } => {
+                check_len("url", url, MAX_URL_BYTES)?;
+                check_not_empty("appid", appid)?;
+                check_not_empty("pagepath", pagepath)
+            }
+            ActionButton::MediaId { media_id, .. } => check_not_empty("media_id", media_id),
+            ActionButton::ArticleId { article_id, .. }
+            | ActionButton::ArticleViewLimited { article_id, .. } => {
+                check_not_empty("article_id", article_id)
+            }
+        }
+    }
+}
+
+impl Menu {
+    /// Checks the menu against WeChat's limits: 1 to 3 top-level buttons, 1
+    /// to 5 buttons per sub menu, names of at most 16 bytes (60 in sub menus),
+    /// keys of at most 128 bytes and URLs of at most 1024 bytes.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` describing the first violation.
+    pub fn validate(&self) -> Result<()> {
+        validate_buttons(&self.button)
+    }
+}
+
+impl ConditionalMenu {
+    /// Checks the buttons like [`Menu::validate`], and that the match rule has
+    /// at least one condition.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` describing the first violation.
+    pub fn validate(&self) -> Result<()> {
+        validate_buttons(&self.button)?;
+
+        if self.matchrule.is_empty() {
+            return Err(WechatError::InvalidArgument(
+                "matchrule must have at least one condition".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl MatchRule {
+    fn is_empty(&self) -> bool {
+        [
+            &self.tag_id,
+            &self.client_platform_type,
+            &self.language,
+            &self.sex,
+            &self.country,
+            &self.province,
+            &self.city,
+        ]
+        .iter()
+        .all(|condition| condition.as_deref().is_none_or(str::is_empty))
+    }
+}
+
+fn validate_buttons(buttons: &[Button]) -> Result<()> {
+    check_count("menu", buttons.len(), MAX_BUTTONS)?;
+
+    for button in buttons {
+        match button {
+            Button::Action(action) => action.validate(MAX_BUTTON_NAME_BYTES)?,
+            Button::Menu(menu) => {
+                check_len("button name", &menu.name, MAX_BUTTON_NAME_BYTES)?;
+                check_count(&menu.name, menu.sub_button.len(), MAX_SUB_BUTTONS)?;
+                for action in &menu.sub_button {
+                    action.validate(MAX_SUB_BUTTON_NAME_BYTES)?;
+                }
+            }
+            Button::Unknown(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn check_count(menu: &str, count: usize, max: usize) -> Result<()> {
+    if count == 0 || count > max {
+        return Err(WechatError::InvalidArgument(format!(
+            "{:?} must have 1 to {} buttons, got {}",
+            menu, max, count
+        )));
+    }
+
+    Ok(())
+}
+
+fn check_not_empty(field: &str, value: &str) -> Result<()> {
+    if value.is_empty() {
+        return Err(WechatError::InvalidArgument(format!("{} is empty", field)));
+    }
+
+    Ok(())
+}
+
+fn check_len(field: &str, value: &str, max_bytes: usize) -> Result<()> {
+    if value.is_empty() || value.len() > max_bytes {
+        return Err(WechatError::InvalidArgument(format!(
+            "{} {:?} must be 1 to {} bytes",
+            field, value, max_bytes
+        )));
+    }
+
+    Ok(())
+}
+
+/// The response of `get_menu`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MenuInfo {
+    pub menu: Menu, // 默认菜单
+    #[serde(default)]
+    pub conditionalmenu: Vec<ConditionalMenu>, // 个性化菜单列表
+}
+
+/// A personalized menu, shown to the users matching `matchrule`.
+///
+/// [个性化菜单接口](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Personalized_menu_interface.html)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalMenu {
+    pub button: Vec<Button>,
+    pub matchrule: MatchRule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub menuid: Option<u64>, // 仅查询时返回
+}
+
+/// The users a conditional menu is shown to. At least one condition must be
+/// set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MatchRule {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_string"
+    )]
+    pub tag_id: Option<String>, // 用户标签的id，可通过用户标签管理接口获取
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_string"
+    )]
+    pub client_platform_type: Option<String>, // 客户端版本，IOS(1), Android(2), Others(3)
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_string"
+    )]
+    pub language: Option<String>, // 语言信息，如 zh_CN、zh_TW、en
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_string"
+    )]
+    pub sex: Option<String>, // 性别：男（1）女（2），已停止匹配
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_string"
+    )]
+    pub country: Option<String>, // 国家信息，已停止匹配
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_string"
+    )]
+    pub province: Option<String>, // 省份信息，已停止匹配
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_string"
+    )]
+    pub city: Option<String>, // 城市信息，已停止匹配
+}
+
+#[derive(Debug, Deserialize)]
+struct AddConditionalMenuResponse {
+    #[serde(deserialize_with = "menuid")]
+    menuid: u64,
+}
+
+/// A value WeChat sends either as a JSON string or as a number.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    Number(u64),
+    String(String),
+}
+
+/// `addconditional` returns the `menuid` as a string, `menu/get` as a number.
+fn menuid<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u64, D::Error> {
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::Number(menuid) => Ok(menuid),
+        StringOrNumber::String(menuid) => menuid.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// `menu/get` returns some match rule conditions, such as `sex` or
+/// `client_platform_type`, as numbers.
+fn optional_string<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    Ok(
+        Option::<StringOrNumber>::deserialize(deserializer)?.map(|value| match value {
+            StringOrNumber::Number(value) => value.to_string(),
+            StringOrNumber::String(value) => value,
+        }),
+    )
+}
+
+/// The response of `get_current_selfmenu_info`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SelfMenuInfoResponse {
+    pub is_menu_open: u8, // 菜单是否开启，0代表未开启，1代表开启
+    pub selfmenu_info: Option<SelfMenuInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SelfMenuInfo {
+    pub button: Vec<SelfMenuButton>,
+}
+
+/// A button of the menu currently in use, created either through the API or
+/// in the WeChat console.
+///
+/// Console menus use the extra types `text`, `img`, `photo`, `video`,
+/// `voice` (with `value`) and `news` (with `news_info`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct SelfMenuButton {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub button_type: Option<String>, // 没有 sub_button 时存在
+    pub key: Option<String>,
+    pub url: Option<String>,
+    pub value: Option<String>,
+    pub appid: Option<String>,
+    pub pagepath: Option<String>,
+    pub news_info: Option<serde_json::Value>,
+    pub sub_button: Option<SelfMenuSubButton>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SelfMenuSubButton {
+    pub list: Vec<SelfMenuButton>,
+}
+
 impl OfficialAccount {
     /// [Deletes all custom menus for the official account](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Deleting_Custom-Defined_Menu.html)
     ///
     /// # Returns
     ///
-    /// * A `Result` containing a `String` with the value `"ok"` on success, or a boxed
-    ///   error on failure.
+    /// * A `Result` containing a `String` with the value `"ok"` on success, or a
+    ///   `WechatError` on failure.
     ///
     /// # Errors
     ///
     /// * Returns an error if the HTTP request fails or returns a non-success status,
-    ///   or if the response cannot be deserialized into a `BasicResponse`.
-    pub async fn delete_menu(&self) -> Result<String, Box<dyn std::error::Error>> {
-        let token = self.token().await?;
-
-        let url = format!("{}{}", DELETE_MENU_URL, token);
-        let response = self.client.post(url).send().await?;
-        if let Err(err) = response.json::<BasicResponse>().await {
-            println!("json err: {:#?}", err);
-            return Err(err.into());
-        }
+    ///   or if WeChat answers with a non-zero `errcode`.
+    pub async fn delete_menu(&self) -> Result<String> {
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(DELETE_MENU_PATH), token);
+            let response = self.client.post(url).send().await?;
+            response::decode::<BasicResponse>(response).await
+        })
+        .await?;
 
         Ok("ok".to_string())
     }
+
+    /// [创建自定义菜单](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Creating_Custom-Defined_Menu.html)
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` if the menu exceeds WeChat's
+    ///   limits, before any request is sent; see [`Menu::validate`].
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode`.
+    pub async fn create_menu(&self, menu: &Menu) -> Result<()> {
+        menu.validate()?;
+
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(CREATE_MENU_PATH), token);
+            let response = self.client.post(url).json(menu).send().await?;
+            response::decode::<BasicResponse>(response).await
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// [创建个性化菜单](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Personalized_menu_interface.html)
+    ///
+    /// A default menu must exist before conditional menus can be added.
+    ///
+    /// # Returns
+    ///
+    /// * The `menuid` of the new menu, used by `del_conditional_menu`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` if the menu exceeds WeChat's
+    ///   limits or the match rule is empty; see [`ConditionalMenu::validate`].
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode`.
+    pub async fn add_conditional_menu(&self, menu: &ConditionalMenu) -> Result<u64> {
+        menu.validate()?;
+
+        let response = self
+            .with_token(|token| async move {
+                let url = format!("{}{}", self.api_url(ADD_CONDITIONAL_MENU_PATH), token);
+                let response = self.client.post(url).json(menu).send().await?;
+                response::decode::<AddConditionalMenuResponse>(response).await
+            })
+            .await?;
+
+        Ok(response.menuid)
+    }
+
+    /// [删除个性化菜单](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Personalized_menu_interface.html)
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode`.
+    pub async fn del_conditional_menu(&self, menuid: u64) -> Result<()> {
+        let body = &serde_json::json!({ "menuid": menuid.to_string() });
+
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(DEL_CONDITIONAL_MENU_PATH), token);
+            let response = self.client.post(url).json(body).send().await?;
+            response::decode::<BasicResponse>(response).await
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// [测试个性化菜单匹配结果](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Personalized_menu_interface.html)
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The openid or WeChat ID of a user.
+    ///
+    /// # Returns
+    ///
+    /// * The menu that user sees.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode`.
+    pub async fn try_match_menu(&self, user_id: &str) -> Result<Menu> {
+        let body = &serde_json::json!({ "user_id": user_id });
+
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(TRY_MATCH_MENU_PATH), token);
+            let response = self.client.post(url).json(body).send().await?;
+            response::decode::<Menu>(response).await
+        })
+        .await
+    }
+
+    /// [查询自定义菜单](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Getting_Custom_Menu_Configurations.html),
+    /// as created through the API.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode` (46003 if there is no menu).
+    pub async fn get_menu(&self) -> Result<MenuInfo> {
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(GET_MENU_PATH), token);
+            let response = self.client.get(url).send().await?;
+            response::decode::<MenuInfo>(response).await
+        })
+        .await
+    }
+
+    /// [查询当前使用的自定义菜单](https://developers.weixin.qq.com/doc/offiaccount/Custom_Menus/Querying_Custom_Menus.html),
+    /// whether created through the API or in the WeChat console.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode`.
+    pub async fn get_current_selfmenu_info(&self) -> Result<SelfMenuInfoResponse> {
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(SELFMENU_INFO_PATH), token);
+            let response = self.client.get(url).send().await?;
+            response::decode::<SelfMenuInfoResponse>(response).await
+        })
+        .await
+    }
 }
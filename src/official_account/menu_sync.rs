@@ -0,0 +1,486 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{OfficialAccount, Result, WechatError};
+
+use super::menu::{Button, ConditionalMenu, MatchRule, Menu, MenuInfo};
+
+/// The `errcode` of `menu/get` when the account has no menu.
+const MENU_NOT_EXIST_ERRCODE: i64 = 46003;
+
+#[cfg(test)]
+mod tests {
+    use super::{DiffLine, MenuDefinition};
+    use crate::WechatError;
+    use crate::test_util::{MockRequest, MockServer};
+
+    const DEFINITION: &str = r#"{
+        "button": [
+            {"type": "click", "name": "今日歌曲", "key": "V1001_TODAY_MUSIC"},
+            {"name": "菜单", "sub_button": [{"type": "view", "name": "搜索", "url": "http://www.soso.com/"}]}
+        ],
+        "conditionalmenu": [
+            {"button": [{"type": "click", "name": "今日歌曲", "key": "V1001_TODAY_MUSIC"}], "matchrule": {"tag_id": "2"}}
+        ]
+    }"#;
+
+    const GET_MENU: &str = r#"{"menu":{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC","sub_button":[]},{"name":"菜单","sub_button":[{"type":"view","name":"搜索","url":"http://www.soso.com/","sub_button":[]}]}],"menuid":208396938},"conditionalmenu":[{"button":[{"type":"click","name":"今日歌曲","key":"V1001_TODAY_MUSIC","sub_button":[]}],"matchrule":{"tag_id":"2","sex":"","country":"","province":"","city":"","client_platform_type":"","language":""},"menuid":208396993}]}"#;
+
+    async fn server(get_menu: &'static str) -> MockServer {
+        MockServer::with_token(move |req| {
+            if req.target.starts_with("/cgi-bin/menu/get") {
+                get_menu.to_string()
+            } else if req.target.starts_with("/cgi-bin/menu/addconditional") {
+                r#"{"menuid":"208396994"}"#.to_string()
+            } else {
+                r#"{"errcode":0,"errmsg":"ok"}"#.to_string()
+            }
+        })
+        .await
+    }
+
+    fn menu_requests(requests: &[MockRequest]) -> Vec<String> {
+        requests
+            .iter()
+            .filter(|req| req.method == "POST")
+            .map(|req| req.target.split('?').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn diff_lines() {
+        let current = MenuDefinition::from_json(DEFINITION).unwrap();
+        let mut desired = current.clone();
+        desired.conditionalmenu.clear();
+
+        let diff = super::MenuDiff::new(&current, &desired).unwrap();
+        assert!(diff.has_changes());
+        assert!(
+            diff.lines()
+                .iter()
+                .all(|line| !matches!(line, DiffLine::Added(_)))
+        );
+        assert!(diff.to_string().contains("-  \"conditionalmenu\": ["));
+
+        let diff = super::MenuDiff::new(&current, &current).unwrap();
+        assert!(!diff.has_changes());
+    }
+
+    #[test]
+    fn parse_json() {
+        let definition = MenuDefinition::from_json(DEFINITION).unwrap();
+        assert_eq!(definition.button.len(), 2);
+        assert_eq!(definition.conditionalmenu.len(), 1);
+
+        assert!(matches!(
+            MenuDefinition::from_json(r#"{"button": [{"type": "click"}]}"#),
+            Err(WechatError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn parse_toml() {
+        let toml = r#"
+            [[button]]
+            type = "click"
+            name = "今日歌曲"
+            key = "V1001_TODAY_MUSIC"
+
+            [[button]]
+            name = "菜单"
+            sub_button = [{ type = "view", name = "搜索", url = "http://www.soso.com/" }]
+
+            [[conditionalmenu]]
+            button = [{ type = "click", name = "今日歌曲", key = "V1001_TODAY_MUSIC" }]
+            matchrule = { tag_id = "2" }
+        "#;
+        assert_eq!(
+            MenuDefinition::from_toml(toml).unwrap(),
+            MenuDefinition::from_json(DEFINITION).unwrap()
+        );
+
+        assert!(matches!(
+            MenuDefinition::from_toml(
+                "[[button]]
+type = \"click\""
+            ),
+            Err(WechatError::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn validate_definition() {
+        assert!(MenuDefinition::default().validate().is_ok());
+
+        let mut definition = MenuDefinition::from_json(DEFINITION).unwrap();
+        assert!(definition.validate().is_ok());
+
+        definition.button.clear();
+        assert!(matches!(
+            definition.validate(),
+            Err(WechatError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn sync_unchanged_menu() {
+        let server = server(GET_MENU).await;
+        let definition = MenuDefinition::from_json(DEFINITION).unwrap();
+
+        let diff = server.account().sync_menu(&definition).await.unwrap();
+        assert!(!diff.has_changes());
+        assert!(menu_requests(&server.requests()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn dry_run() {
+        let server = server(GET_MENU).await;
+        let mut definition = MenuDefinition::from_json(DEFINITION).unwrap();
+        definition.button.truncate(1);
+
+        let diff = server.account().diff_menu(&definition).await.unwrap();
+        assert!(diff.has_changes());
+        assert!(diff.to_string().contains("-      \"name\": \"菜单\","));
+        assert!(menu_requests(&server.requests()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_changed_menus() {
+        let server = server(GET_MENU).await;
+        let mut definition = MenuDefinition::from_json(DEFINITION).unwrap();
+        definition.button.truncate(1);
+
+        server.account().sync_menu(&definition).await.unwrap();
+        assert_eq!(menu_requests(&server.requests()), ["/cgi-bin/menu/create"]);
+
+        let server = self::server(GET_MENU).await;
+        let mut definition = MenuDefinition::from_json(DEFINITION).unwrap();
+        definition.conditionalmenu[0].matchrule.tag_id = Some("3".to_string());
+
+        server.account().sync_menu(&definition).await.unwrap();
+        let requests = server.requests();
+        assert_eq!(
+            menu_requests(&requests),
+            [
+                "/cgi-bin/menu/delconditional",
+                "/cgi-bin/menu/addconditional"
+            ]
+        );
+        let del = requests
+            .iter()
+            .find(|req| req.target.starts_with("/cgi-bin/menu/delconditional"))
+            .unwrap();
+        assert_eq!(del.body, r#"{"menuid":"208396993"}"#);
+    }
+
+    #[tokio::test]
+    async fn sync_without_menu() {
+        let server = server(r#"{"errcode":46003,"errmsg":"menu no exist"}"#).await;
+        let definition = MenuDefinition::from_json(DEFINITION).unwrap();
+
+        server.account().sync_menu(&definition).await.unwrap();
+        assert_eq!(
+            menu_requests(&server.requests()),
+            ["/cgi-bin/menu/create", "/cgi-bin/menu/addconditional"]
+        );
+
+        let server = self::server(GET_MENU).await;
+        server
+            .account()
+            .sync_menu(&MenuDefinition::default())
+            .await
+            .unwrap();
+        assert_eq!(menu_requests(&server.requests()), ["/cgi-bin/menu/delete"]);
+    }
+}
+
+/// The menus an account should have, in the JSON layout of `menu/create`
+/// with an optional `conditionalmenu` list in the layout of
+/// `menu/addconditional`, or the same structure in TOML.
+///
+/// An empty `button` list means the account should have no menu at all.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MenuDefinition {
+    #[serde(default)]
+    pub button: Vec<Button>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditionalmenu: Vec<ConditionalMenu>,
+}
+
+impl MenuDefinition {
+    /// Parses a definition from JSON.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::Json` if `json` is not a valid definition.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Parses a definition from TOML, with the same keys as the JSON layout:
+    ///
+    /// ```toml
+    /// [[button]]
+    /// type = "click"
+    /// name = "今日歌曲"
+    /// key = "V1001_TODAY_MUSIC"
+    ///
+    /// [[button]]
+    /// name = "菜单"
+    /// sub_button = [{ type = "view", name = "搜索", url = "http://www.soso.com/" }]
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::Toml` if `toml` is not a valid definition.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Checks every menu like [`Menu::validate`] and
+    /// [`ConditionalMenu::validate`].
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` describing the first violation,
+    ///   or if there are conditional menus but no default menu.
+    pub fn validate(&self) -> Result<()> {
+        if self.button.is_empty() {
+            if !self.conditionalmenu.is_empty() {
+                return Err(WechatError::InvalidArgument(
+                    "conditional menus require a default menu".to_string(),
+                ));
+            }
+            return Ok(());
+        }
+
+        self.menu().validate()?;
+        for menu in &self.conditionalmenu {
+            menu.validate()?;
+        }
+
+        Ok(())
+    }
+
+    fn menu(&self) -> Menu {
+        Menu {
+            button: self.button.clone(),
+            menuid: None,
+        }
+    }
+
+    /// Drops what WeChat adds to the menus it returns, the `menuid`s and the
+    /// empty match rule conditions, so that definitions compare by content.
+    fn normalized(mut self) -> Self {
+        for menu in &mut self.conditionalmenu {
+            menu.menuid = None;
+            normalize_rule(&mut menu.matchrule);
+        }
+        self
+    }
+}
+
+impl From<MenuInfo> for MenuDefinition {
+    fn from(info: MenuInfo) -> Self {
+        MenuDefinition {
+            button: info.menu.button,
+            conditionalmenu: info.conditionalmenu,
+        }
+    }
+}
+
+fn normalize_rule(rule: &mut MatchRule) {
+    for condition in [
+        &mut rule.tag_id,
+        &mut rule.client_platform_type,
+        &mut rule.language,
+        &mut rule.sex,
+        &mut rule.country,
+        &mut rule.province,
+        &mut rule.city,
+    ] {
+        condition.take_if(|value| value.is_empty());
+    }
+}
+
+/// A line of a [`MenuDiff`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// The difference between the menus of an account and a [`MenuDefinition`],
+/// as a line diff of their pretty-printed JSON.
+///
+/// `Display` prints it in the unified diff style, prefixing removed lines
+/// with `-` and added lines with `+`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuDiff {
+    lines: Vec<DiffLine>,
+}
+
+impl MenuDiff {
+    fn new(current: &MenuDefinition, desired: &MenuDefinition) -> Result<Self> {
+        let current = serde_json::to_string_pretty(current)?;
+        let desired = serde_json::to_string_pretty(desired)?;
+        let old: Vec<&str> = current.lines().collect();
+        let new: Vec<&str> = desired.lines().collect();
+
+        // lcs[i][j] is the length of the longest common subsequence of
+        // old[i..] and new[j..].
+        let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for (i, old_line) in old.iter().enumerate().rev() {
+            for (j, new_line) in new.iter().enumerate().rev() {
+                lcs[i][j] = if old_line == new_line {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut lines = Vec::with_capacity(old.len().max(new.len()));
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() && j < new.len() {
+            if old[i] == new[j] {
+                lines.push(DiffLine::Same(old[i].to_string()));
+                i += 1;
+                j += 1;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                lines.push(DiffLine::Removed(old[i].to_string()));
+                i += 1;
+            } else {
+                lines.push(DiffLine::Added(new[j].to_string()));
+                j += 1;
+            }
+        }
+        lines.extend(
+            old[i..]
+                .iter()
+                .map(|line| DiffLine::Removed(line.to_string())),
+        );
+        lines.extend(
+            new[j..]
+                .iter()
+                .map(|line| DiffLine::Added(line.to_string())),
+        );
+
+        Ok(MenuDiff { lines })
+    }
+
+    /// Returns `true` if the account's menus differ from the definition.
+    pub fn has_changes(&self) -> bool {
+        self.lines
+            .iter()
+            .any(|line| !matches!(line, DiffLine::Same(_)))
+    }
+
+    pub fn lines(&self) -> &[DiffLine] {
+        &self.lines
+    }
+}
+
+impl fmt::Display for MenuDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                DiffLine::Same(line) => writeln!(f, " {}", line)?,
+                DiffLine::Removed(line) => writeln!(f, "-{}", line)?,
+                DiffLine::Added(line) => writeln!(f, "+{}", line)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OfficialAccount {
+    /// Compares the account's menus, as returned by `get_menu`, with
+    /// `definition` without changing anything.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` if the definition exceeds
+    ///   WeChat's limits; see [`MenuDefinition::validate`].
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode` other than 46003 (no menu).
+    pub async fn diff_menu(&self, definition: &MenuDefinition) -> Result<MenuDiff> {
+        definition.validate()?;
+
+        let current = self.current_menu().await?;
+        MenuDiff::new(
+            &MenuDefinition::from(current).normalized(),
+            &definition.clone().normalized(),
+        )
+    }
+
+    /// Brings the account's menus in line with `definition`, sending requests
+    /// only for the parts that differ:
+    ///
+    /// * an empty definition deletes all menus;
+    /// * a changed default menu is recreated with `create_menu`;
+    /// * changed conditional menus are all deleted and added again, in order.
+    ///
+    /// # Returns
+    ///
+    /// * The differences found, which are empty if nothing was changed.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` if the definition exceeds
+    ///   WeChat's limits, before any request changes the menus; see
+    ///   [`MenuDefinition::validate`].
+    /// * Returns an error if an HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode` other than 46003 (no menu).
+    ///   The menus may then be partially updated; syncing again resumes.
+    pub async fn sync_menu(&self, definition: &MenuDefinition) -> Result<MenuDiff> {
+        definition.validate()?;
+
+        let current_info = self.current_menu().await?;
+        let menuids: Vec<u64> = current_info
+            .conditionalmenu
+            .iter()
+            .filter_map(|menu| menu.menuid)
+            .collect();
+        let current = MenuDefinition::from(current_info).normalized();
+        let desired = definition.clone().normalized();
+
+        let diff = MenuDiff::new(&current, &desired)?;
+        if !diff.has_changes() {
+            return Ok(diff);
+        }
+
+        if desired.button.is_empty() {
+            self.delete_menu().await?;
+            return Ok(diff);
+        }
+
+        if desired.button != current.button {
+            self.create_menu(&desired.menu()).await?;
+        }
+
+        if desired.conditionalmenu != current.conditionalmenu {
+            for menuid in menuids {
+                self.del_conditional_menu(menuid).await?;
+            }
+            for menu in &desired.conditionalmenu {
+                self.add_conditional_menu(menu).await?;
+            }
+        }
+
+        Ok(diff)
+    }
+
+    /// Returns the account's menus, which are empty if it has none.
+    async fn current_menu(&self) -> Result<MenuInfo> {
+        match self.get_menu().await {
+            Err(err) if err.errcode() == Some(MENU_NOT_EXIST_ERRCODE) => Ok(MenuInfo {
+                menu: Menu::default(),
+                conditionalmenu: Vec::new(),
+            }),
+            result => result,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use chrono::Utc;
-use quick_xml::{de::from_str, se};
+use quick_xml::de::from_str;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
+use std::sync::Arc;
 
 use actix_web::{
     FromRequest, HttpRequest,
@@ -10,7 +11,153 @@ use actix_web::{
     web::{self},
 };
 
-use super::signature::signature;
+use crate::{OfficialAccount, WechatError};
+
+use super::crypto::{EncryptedEnvelope, MessageCrypto};
+use super::inbound::InboundMessage;
+use super::registry::resolve_account;
+use super::signature::{WechatQuery, signature};
+use super::xml::write_reply;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{FromRequest, test::TestRequest, web};
+
+    use quick_xml::se;
+
+    use super::{Article, MessageHandler, MusicReply};
+    use crate::OfficialAccount;
+    use crate::WechatError;
+    use crate::cache::MemoryCache;
+    use crate::official_account::inbound::InboundMessage;
+    use crate::official_account::{crypto::EncryptedEnvelope, signature::signature};
+    use crate::test_util::{ENCODING_AES_KEY, ENCRYPTED, inbound, test_config};
+
+    fn account() -> OfficialAccount {
+        let mut config = test_config();
+        config.encoding_aes_key = Some(ENCODING_AES_KEY.to_string());
+        OfficialAccount::with_cache(config, Arc::new(MemoryCache::new()))
+    }
+
+    #[actix_web::test]
+    async fn extract_encrypted_message() {
+        let uri = format!(
+            "/wechat?timestamp=1409735669&nonce=1320562132&signature={}&encrypt_type=aes&msg_signature=aba6dcd6bfb4cc5aed4a8b527503579881712f3d",
+            signature("wechat", "1409735669", "1320562132")
+        );
+        let body = format!(
+            "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><Encrypt><![CDATA[{}]]></Encrypt></xml>",
+            ENCRYPTED
+        );
+        let (req, mut payload) = TestRequest::post()
+            .uri(&uri)
+            .app_data(web::Data::new(account()))
+            .set_payload(body)
+            .to_http_parts();
+
+        let handler = MessageHandler::from_request(&req, &mut payload)
+            .await
+            .unwrap();
+        assert!(handler.is_encrypted());
+        let InboundMessage::Text(text) = &handler.message else {
+            panic!("expected a text message");
+        };
+        assert_eq!(text.content, "hello");
+
+        let reply = handler
+            .to_string(&handler.message.plaintext("world"))
+            .unwrap();
+        let envelope = quick_xml::de::from_str::<EncryptedEnvelope>(&reply).unwrap();
+        let decrypted = account()
+            .message_crypto()
+            .unwrap()
+            .decrypt(&envelope.encrypt)
+            .unwrap();
+        assert!(decrypted.contains("<Content><![CDATA[world]]></Content>"));
+    }
+
+    #[test]
+    fn media_replies() {
+        let xml = se::to_string(&inbound().image("media_id")).unwrap();
+        assert!(xml.contains(
+            "<ToUserName>oUser</ToUserName><FromUserName>gh_123456789abc</FromUserName>"
+        ));
+        assert!(xml.contains("<MsgType>image</MsgType><Image><MediaId>media_id</MediaId></Image>"));
+        assert!(!xml.contains("<Content>"));
+
+        let xml = se::to_string(&inbound().voice("media_id")).unwrap();
+        assert!(xml.contains("<MsgType>voice</MsgType><Voice><MediaId>media_id</MediaId></Voice>"));
+
+        let xml = se::to_string(&inbound().video("media_id", Some("title"), None)).unwrap();
+        assert!(xml.contains(
+            "<MsgType>video</MsgType><Video><MediaId>media_id</MediaId><Title>title</Title></Video>"
+        ));
+    }
+
+    #[test]
+    fn music_reply() {
+        let reply = inbound().music(MusicReply {
+            title: Some("title".to_string()),
+            music_url: Some("http://example.com/a.mp3".to_string()),
+            thumb_media_id: "thumb".to_string(),
+            ..Default::default()
+        });
+        let xml = se::to_string(&reply).unwrap();
+        assert!(xml.contains(
+            "<Music><Title>title</Title><MusicUrl>http://example.com/a.mp3</MusicUrl><ThumbMediaId>thumb</ThumbMediaId></Music>"
+        ));
+    }
+
+    #[test]
+    fn news_reply() {
+        let article = Article {
+            title: "title".to_string(),
+            description: "description".to_string(),
+            pic_url: "http://example.com/a.jpg".to_string(),
+            url: "http://example.com".to_string(),
+        };
+        let reply = inbound()
+            .news(vec![article.clone(), article.clone()])
+            .unwrap();
+        let xml = se::to_string(&reply).unwrap();
+        assert!(xml.contains("<MsgType>news</MsgType><ArticleCount>2</ArticleCount><Articles><item><Title>title</Title><Description>description</Description><PicUrl>http://example.com/a.jpg</PicUrl><Url>http://example.com</Url></item><item>"));
+
+        let err = inbound().news(vec![article; 9]).unwrap_err();
+        assert!(matches!(err, WechatError::InvalidArgument(_)));
+        assert!(inbound().news(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn transfer_customer_service_reply() {
+        let xml = se::to_string(&inbound().transfer_customer_service(None)).unwrap();
+        assert!(xml.ends_with("<MsgType>transfer_customer_service</MsgType></xml>"));
+
+        let xml = se::to_string(&inbound().transfer_customer_service(Some("test1@test"))).unwrap();
+        assert!(xml.contains("<TransInfo><KfAccount>test1@test</KfAccount></TransInfo>"));
+    }
+
+    #[actix_web::test]
+    async fn reject_bad_msg_signature() {
+        let uri = format!(
+            "/wechat?timestamp=1409735669&nonce=1320562132&signature={}&encrypt_type=aes&msg_signature=bad",
+            signature("wechat", "1409735669", "1320562132")
+        );
+        let body = format!("<xml><Encrypt><![CDATA[{}]]></Encrypt></xml>", ENCRYPTED);
+        let (req, mut payload) = TestRequest::post()
+            .uri(&uri)
+            .app_data(web::Data::new(account()))
+            .set_payload(body)
+            .to_http_parts();
+
+        assert!(
+            MessageHandler::from_request(&req, &mut payload)
+                .await
+                .is_err()
+        );
+    }
+}
 
 pub struct MsgType;
 
@@ -48,35 +195,52 @@ impl EventType {
     pub const SUBSCRIBE: &str = "subscribe";
     // UNSUBSCRIBE 取消订阅
     pub const UNSUBSCRIBE: &str = "unsubscribe";
-    // SCAN 取消订阅
+    // SCAN 已关注用户扫描带参数二维码
     pub const SCAN: &str = "SCAN";
+    // LOCATION 上报地理位置
+    pub const LOCATION: &str = "LOCATION";
+    // CLICK 点击菜单拉取消息
+    pub const CLICK: &str = "CLICK";
+    // VIEW 点击菜单跳转链接
+    pub const VIEW: &str = "VIEW";
+    // SCANCODE_PUSH 扫码推事件
+    pub const SCANCODE_PUSH: &str = "scancode_push";
+    // SCANCODE_WAITMSG 扫码推事件且弹出"消息接收中"提示框
+    pub const SCANCODE_WAITMSG: &str = "scancode_waitmsg";
+    // PIC_SYSPHOTO 弹出系统拍照发图
+    pub const PIC_SYSPHOTO: &str = "pic_sysphoto";
+    // PIC_PHOTO_OR_ALBUM 弹出拍照或者相册发图
+    pub const PIC_PHOTO_OR_ALBUM: &str = "pic_photo_or_album";
+    // PIC_WEIXIN 弹出微信相册发图器
+    pub const PIC_WEIXIN: &str = "pic_weixin";
+    // LOCATION_SELECT 弹出地理位置选择器
+    pub const LOCATION_SELECT: &str = "location_select";
+    // VIEW_MINIPROGRAM 点击菜单跳转小程序
+    pub const VIEW_MINIPROGRAM: &str = "view_miniprogram";
+    // TEMPLATE_SEND_JOB_FINISH 模板消息发送结果
+    pub const TEMPLATE_SEND_JOB_FINISH: &str = "TEMPLATESENDJOBFINISH";
+    // MASS_SEND_JOB_FINISH 群发结果
+    pub const MASS_SEND_JOB_FINISH: &str = "MASSSENDJOBFINISH";
+    // SUBSCRIBE_MSG_POPUP 用户操作订阅通知弹窗
+    pub const SUBSCRIBE_MSG_POPUP: &str = "subscribe_msg_popup_event";
+    // SUBSCRIBE_MSG_CHANGE 用户管理订阅通知
+    pub const SUBSCRIBE_MSG_CHANGE: &str = "subscribe_msg_change_event";
+    // SUBSCRIBE_MSG_SENT 发送订阅通知结果
+    pub const SUBSCRIBE_MSG_SENT: &str = "subscribe_msg_sent_event";
+    // KF_CREATE_SESSION 客服接入会话
+    pub const KF_CREATE_SESSION: &str = "kf_create_session";
+    // KF_CLOSE_SESSION 客服关闭会话
+    pub const KF_CLOSE_SESSION: &str = "kf_close_session";
+    // KF_SWITCH_SESSION 客服转接会话
+    pub const KF_SWITCH_SESSION: &str = "kf_switch_session";
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename = "xml")]
-pub struct WechatMessage {
-    #[serde(rename = "ToUserName")]
-    pub to_user_name: String, // 微信号(公众号原始id)
-    #[serde(rename = "FromUserName")]
-    pub from_user_name: String, // 发送方账号（一个OpenID）
-    #[serde(rename = "CreateTime")]
-    pub create_time: u64, // 消息创建时间 （整型）
-    #[serde(rename = "MsgType")]
-    pub msg_type: String, // 消息类型，文本为text
-    #[serde(rename = "Content")]
-    pub content: Option<String>, // 文本消息内容
-    #[serde(rename = "MsgId")]
-    pub msg_id: Option<u64>, // 消息id，64位整型
-    #[serde(rename = "Idx")]
-    pub idx: Option<u64>, // 多图文时第几篇文章，从1开始（消息如果来自文章时才有）
-    #[serde(rename = "Event")]
-    pub event: Option<String>, // 事件类型，subscribe
-    #[serde(rename = "EventKey")]
-    pub event_key: Option<String>, // 事件KEY值，qrscene_为前缀，后面为二维码的场景值ID
-    #[serde(rename = "Ticket")]
-    pub ticket: Option<String>, // 二维码的ticket，可用来换取二维码图片
-}
+/// Maximum number of articles in a news reply.
+pub const MAX_NEWS_ARTICLES: usize = 8;
 
+/// A passive reply, built from the message it answers.
+///
+/// [被动回复用户消息](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Passive_user_reply_message.html)
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename = "xml")]
 pub struct WeChatResponse {
@@ -88,53 +252,241 @@ pub struct WeChatResponse {
     pub create_time: u64,
     #[serde(rename = "MsgType")]
     pub msg_type: String,
-    #[serde(rename = "Content")]
-    pub content: String,
+    #[serde(rename = "Content", skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(rename = "Image", skip_serializing_if = "Option::is_none")]
+    pub image: Option<MediaReply>,
+    #[serde(rename = "Voice", skip_serializing_if = "Option::is_none")]
+    pub voice: Option<MediaReply>,
+    #[serde(rename = "Video", skip_serializing_if = "Option::is_none")]
+    pub video: Option<VideoReply>,
+    #[serde(rename = "Music", skip_serializing_if = "Option::is_none")]
+    pub music: Option<MusicReply>,
+    #[serde(rename = "ArticleCount", skip_serializing_if = "Option::is_none")]
+    pub article_count: Option<usize>,
+    #[serde(rename = "Articles", skip_serializing_if = "Option::is_none")]
+    pub articles: Option<Articles>,
+    #[serde(rename = "TransInfo", skip_serializing_if = "Option::is_none")]
+    pub trans_info: Option<TransInfo>,
+}
+
+impl WeChatResponse {
+    /// Serializes the reply in the layout of the WeChat docs, with every string
+    /// field wrapped in CDATA.
+    pub fn to_xml(&self) -> crate::Result<String> {
+        write_reply(self)
+    }
+}
+
+/// The `<Image>` or `<Voice>` element of a reply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaReply {
+    #[serde(rename = "MediaId")]
+    pub media_id: String, // 通过素材管理中的接口上传多媒体文件，得到的id
+}
+
+/// The `<Video>` element of a reply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoReply {
+    #[serde(rename = "MediaId")]
+    pub media_id: String,
+    #[serde(rename = "Title", skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>, // 视频消息的标题
+    #[serde(rename = "Description", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>, // 视频消息的描述
+}
+
+/// The `<Music>` element of a reply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MusicReply {
+    #[serde(rename = "Title", skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>, // 音乐标题
+    #[serde(rename = "Description", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>, // 音乐描述
+    #[serde(rename = "MusicUrl", skip_serializing_if = "Option::is_none")]
+    pub music_url: Option<String>, // 音乐链接
+    #[serde(rename = "HQMusicUrl", skip_serializing_if = "Option::is_none")]
+    pub hq_music_url: Option<String>, // 高质量音乐链接，WIFI环境优先使用该链接播放音乐
+    #[serde(rename = "ThumbMediaId")]
+    pub thumb_media_id: String, // 缩略图的媒体id
+}
+
+/// The `<Articles>` element of a news reply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Articles {
+    #[serde(rename = "item")]
+    pub items: Vec<Article>,
+}
+
+/// An `<item>` of a news reply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Article {
+    #[serde(rename = "Title")]
+    pub title: String, // 图文消息标题
+    #[serde(rename = "Description")]
+    pub description: String, // 图文消息描述
+    #[serde(rename = "PicUrl")]
+    pub pic_url: String, // 图片链接，较好的效果为大图360*200，小图200*200
+    #[serde(rename = "Url")]
+    pub url: String, // 点击图文消息跳转链接
+}
+
+/// The `<TransInfo>` element of a `transfer_customer_service` reply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransInfo {
+    #[serde(rename = "KfAccount")]
+    pub kf_account: String, // 指定会话接入的客服账号
 }
 
 pub struct MessageHandler {
-    pub message: WechatMessage,
+    pub message: InboundMessage,
+    /// The account the message was sent to.
+    account: Arc<OfficialAccount>,
+    /// Set when the request was encrypted, so that the reply is encrypted too.
+    crypto: Option<MessageCrypto>,
 }
 
 impl MessageHandler {
-    pub fn to_string(
-        &self,
-        message: &WeChatResponse,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        match se::to_string(message) {
-            Ok(s) => Ok(s),
-            Err(e) => Err(e.into()),
+    pub(crate) fn new(
+        message: InboundMessage,
+        account: Arc<OfficialAccount>,
+        crypto: Option<MessageCrypto>,
+    ) -> Self {
+        MessageHandler {
+            message,
+            account,
+            crypto,
+        }
+    }
+
+    /// Serializes a reply, encrypting it when the request was encrypted.
+    pub fn to_string(&self, message: &WeChatResponse) -> crate::Result<String> {
+        let xml = message.to_xml()?;
+
+        match &self.crypto {
+            Some(crypto) => {
+                let timestamp = Utc::now().timestamp().to_string();
+                let nonce = rand::random::<u32>().to_string();
+                crypto.encrypt_reply(&xml, &timestamp, &nonce)
+            }
+            None => Ok(xml),
         }
     }
-}
 
-impl WechatMessage {
-    pub fn get_open_id(&self) -> String {
-        self.from_user_name.clone()
+    /// Returns the account the message was sent to.
+    pub fn account(&self) -> &Arc<OfficialAccount> {
+        &self.account
     }
 
-    pub fn get_gh_id(&self) -> String {
-        self.to_user_name.clone()
+    /// Returns `true` if the request was encrypted ("安全模式" or "兼容模式").
+    pub fn is_encrypted(&self) -> bool {
+        self.crypto.is_some()
     }
+}
 
+impl InboundMessage {
+    /// Replies with a text message.
     pub fn plaintext(&self, content: &str) -> WeChatResponse {
-        let now = Utc::now().naive_utc();
         WeChatResponse {
-            to_user_name: self.from_user_name.clone(),
-            from_user_name: self.to_user_name.clone(),
-            create_time: now.and_utc().timestamp() as u64,
-            msg_type: MsgType::TEXT.to_string(),
-            content: content.to_string(),
+            content: Some(content.to_string()),
+            ..self.reply(MsgType::TEXT)
         }
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WechatQuery {
-    timestamp: String,
-    nonce: String,
-    signature: String,
-    echostr: Option<String>, // 验证时才需要
+    /// Replies with an image uploaded as media `media_id`.
+    pub fn image(&self, media_id: &str) -> WeChatResponse {
+        WeChatResponse {
+            image: Some(MediaReply {
+                media_id: media_id.to_string(),
+            }),
+            ..self.reply(MsgType::IMAGE)
+        }
+    }
+
+    /// Replies with a voice message uploaded as media `media_id`.
+    pub fn voice(&self, media_id: &str) -> WeChatResponse {
+        WeChatResponse {
+            voice: Some(MediaReply {
+                media_id: media_id.to_string(),
+            }),
+            ..self.reply(MsgType::VOICE)
+        }
+    }
+
+    /// Replies with a video uploaded as media `media_id`.
+    pub fn video(
+        &self,
+        media_id: &str,
+        title: Option<&str>,
+        description: Option<&str>,
+    ) -> WeChatResponse {
+        WeChatResponse {
+            video: Some(VideoReply {
+                media_id: media_id.to_string(),
+                title: title.map(str::to_string),
+                description: description.map(str::to_string),
+            }),
+            ..self.reply(MsgType::VIDEO)
+        }
+    }
+
+    /// Replies with a music message.
+    pub fn music(&self, music: MusicReply) -> WeChatResponse {
+        WeChatResponse {
+            music: Some(music),
+            ..self.reply(MsgType::MUSIC)
+        }
+    }
+
+    /// Replies with a news message.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` if `articles` is empty or has
+    ///   more than 8 items.
+    pub fn news(&self, articles: Vec<Article>) -> crate::Result<WeChatResponse> {
+        if articles.is_empty() || articles.len() > MAX_NEWS_ARTICLES {
+            return Err(WechatError::InvalidArgument(format!(
+                "a news reply must have 1 to {} articles, got {}",
+                MAX_NEWS_ARTICLES,
+                articles.len()
+            )));
+        }
+
+        Ok(WeChatResponse {
+            article_count: Some(articles.len()),
+            articles: Some(Articles { items: articles }),
+            ..self.reply(MsgType::NEWS)
+        })
+    }
+
+    /// Forwards the message to customer service, optionally to the given
+    /// `KfAccount` (e.g. `test1@test`).
+    pub fn transfer_customer_service(&self, kf_account: Option<&str>) -> WeChatResponse {
+        WeChatResponse {
+            trans_info: kf_account.map(|kf_account| TransInfo {
+                kf_account: kf_account.to_string(),
+            }),
+            ..self.reply(MsgType::TRANSFER)
+        }
+    }
+
+    fn reply(&self, msg_type: &str) -> WeChatResponse {
+        WeChatResponse {
+            to_user_name: self.from_user_name().to_string(),
+            from_user_name: self.to_user_name().to_string(),
+            create_time: Utc::now().timestamp() as u64,
+            msg_type: msg_type.to_string(),
+            content: None,
+            image: None,
+            voice: None,
+            video: None,
+            music: None,
+            article_count: None,
+            articles: None,
+            trans_info: None,
+        }
+    }
 }
 
 impl FromRequest for MessageHandler {
@@ -153,24 +505,55 @@ impl FromRequest for MessageHandler {
                 ))
             });
 
+        let req = req.clone();
+
         Box::pin(async move {
             let query = match query_future {
                 Ok(q) => q,
                 Err(e) => return Err(e),
             };
 
-            // TODO: token use official account
-            if query.signature != signature("wechat", &query.timestamp, &query.nonce) {
+            let bytes = fut.await?;
+            let xml_str = String::from_utf8_lossy(&bytes);
+
+            let account = resolve_account(&req, Some(&xml_str))?;
+            let token = &account.config.token;
+            if query.signature != signature(token, &query.timestamp, &query.nonce) {
                 return Err(error::ErrorUnauthorized("Invalid signature"));
             }
 
-            let bytes = fut.await?;
-            let xml_str = String::from_utf8_lossy(&bytes);
+            let (xml_str, crypto) = if query.is_encrypted() {
+                let crypto = account
+                    .message_crypto()
+                    .map_err(error::ErrorInternalServerError)?;
+
+                let envelope = from_str::<EncryptedEnvelope>(&xml_str)
+                    .map_err(|e| error::ErrorBadRequest(format!("Invalid XML input: {}", e)))?;
+                let msg_signature = query
+                    .msg_signature
+                    .as_deref()
+                    .ok_or_else(|| error::ErrorUnauthorized("Missing msg_signature"))?;
+                crypto
+                    .verify(
+                        msg_signature,
+                        &query.timestamp,
+                        &query.nonce,
+                        &envelope.encrypt,
+                    )
+                    .map_err(error::ErrorUnauthorized)?;
+
+                let xml_str = crypto
+                    .decrypt(&envelope.encrypt)
+                    .map_err(error::ErrorBadRequest)?;
+                (xml_str, Some(crypto))
+            } else {
+                (xml_str.into_owned(), None)
+            };
 
-            let message = from_str::<WechatMessage>(&xml_str)
+            let message = InboundMessage::from_xml(&xml_str)
                 .map_err(|e| error::ErrorBadRequest(format!("Invalid XML input: {}", e)))?;
 
-            Ok(MessageHandler { message })
+            Ok(MessageHandler::new(message, account, crypto))
         })
     }
 }
@@ -1,8 +1,17 @@
 pub mod core;
+pub mod crypto;
+pub mod custom;
+pub mod event;
+pub mod inbound;
 pub mod menu;
+pub mod menu_sync;
 pub mod message;
 pub mod qrcode;
 pub mod quota;
+pub mod registry;
+mod response;
+pub mod router;
 pub mod signature;
 pub mod token;
 pub mod user;
+mod xml;
@@ -1,15 +1,17 @@
 use std::{any::TypeId, str::FromStr};
 
-use crate::OfficialAccount;
+use crate::constants::urls;
+use crate::{OfficialAccount, Result};
 
 use serde::{Deserialize, Serialize};
 use urlencoding::encode;
 
-pub(crate) const QR_CREATE_URL: &str =
-    "https://api.weixin.qq.com/cgi-bin/qrcode/create?access_token=";
-pub(crate) const QR_IMG_URL: &str = "https://mp.weixin.qq.com/cgi-bin/showqrcode?ticket=";
+use super::response;
 
-#[cfg(test)]
+pub(crate) const QR_CREATE_PATH: &str = "/cgi-bin/qrcode/create?access_token=";
+pub(crate) const QR_IMG_PATH: &str = "/cgi-bin/showqrcode?ticket=";
+
+#[cfg(all(test, feature = "redis"))]
 mod tests {
     use uuid::Uuid;
 
@@ -36,7 +38,7 @@ mod tests {
         };
         let account = OfficialAccount::new(config, redis_url);
 
-        let key = format!("login:{}", Uuid::new_v4().to_string());
+        let key = format!("login:{}", Uuid::new_v4());
 
         let scene = qrcode::TicketScene {
             scene_id: None,
@@ -49,7 +51,7 @@ mod tests {
         };
 
         let at = account.get_qr_ticket(params).await;
-        println!("get_qr_ticket: {:#?}", at.unwrap().show_qrcode());
+        println!("get_qr_ticket: {:#?}", account.show_qrcode(&at.unwrap()));
     }
 }
 
@@ -129,14 +131,19 @@ impl TicketResponse {
     /// Generates a URL to display the QR code using the ticket.
     ///
     /// This function encodes the ticket and constructs a URL that can be used to
-    /// view the QR code associated with the provided ticket.
+    /// view the QR code associated with the provided ticket. The URL always
+    /// points at `mp.weixin.qq.com`; [`OfficialAccount::show_qrcode`] uses the
+    /// configured base URL instead.
     ///
     /// # Returns
     ///
     /// * A `String` representing the URL to display the QR code.
+    #[deprecated(
+        note = "use `OfficialAccount::show_qrcode`, which honours the configured base URL"
+    )]
     pub fn show_qrcode(&self) -> String {
         let ticket = encode(&self.ticket);
-        format!("{}{}", QR_IMG_URL, ticket)
+        format!("{}{}{}", urls::MP_BASE_URL, QR_IMG_PATH, ticket)
     }
 }
 
@@ -149,31 +156,22 @@ impl OfficialAccount {
     /// # Errors
     ///
     /// * Returns an error if the HTTP request fails or returns a non-success status,
-    ///   or if the response cannot be deserialized into a `String`.
-    pub async fn get_qr_ticket(
-        &self,
-        req: TicketRequest,
-    ) -> Result<TicketResponse, Box<dyn std::error::Error>> {
-        println!("req: {:#?}", &req);
-        let token = self.token().await?;
-
-        let url = format!("{}{}", QR_CREATE_URL, token);
-        let response = match self.client.post(url).json(&req).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                println!("json err: {:#?}", e);
-                return Err(e.into());
-            }
-        };
-
-        let result = match response.json::<TicketResponse>().await {
-            Ok(r) => r,
-            Err(e) => {
-                println!("json err: {:#?}", e);
-                return Err(e.into());
-            }
-        };
+    ///   if WeChat answers with a non-zero `errcode`, or if the response cannot be
+    ///   deserialized into a `TicketResponse`.
+    pub async fn get_qr_ticket(&self, req: TicketRequest) -> Result<TicketResponse> {
+        let req = &req;
+
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(QR_CREATE_PATH), token);
+            let response = self.client.post(url).json(req).send().await?;
+            response::decode(response).await
+        })
+        .await
+    }
 
-        Ok(result)
+    /// Generates a URL to display the QR code of `ticket`, using the configured
+    /// `mp.weixin.qq.com` base URL.
+    pub fn show_qrcode(&self, ticket: &TicketResponse) -> String {
+        format!("{}{}", self.mp_url(QR_IMG_PATH), encode(&ticket.ticket))
     }
 }
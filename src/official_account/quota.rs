@@ -1,12 +1,12 @@
 use std::collections::HashMap;
 
-use crate::OfficialAccount;
+use crate::{OfficialAccount, Result};
 
-use super::core::BasicResponse;
+use super::{core::BasicResponse, response};
 
-pub(crate) const CLEAR_QUOTA_URL: &str = "https://api.weixin.qq.com/cgi-bin/clear_quota?access_token=";
+pub(crate) const CLEAR_QUOTA_PATH: &str = "/cgi-bin/clear_quota?access_token=";
 
-#[cfg(test)]
+#[cfg(all(test, feature = "redis"))]
 mod tests {
 
     use crate::{Config, OfficialAccount};
@@ -35,25 +35,17 @@ mod tests {
 
 impl OfficialAccount {
     /// [清空api的调用quota](https://developers.weixin.qq.com/doc/offiaccount/openApi/clear_quota.html)
-    pub async fn clear_quota(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let token = self.token().await?;
-
+    pub async fn clear_quota(&self) -> Result<()> {
         let mut params = HashMap::new();
         params.insert("appid".to_string(), self.config.appid.clone());
-
-        let url = format!("{}{}", CLEAR_QUOTA_URL, token);
-        let response = match self.client.post(url).json(&params).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                println!("json err: {:#?}", e);
-                return Err(e.into());
-            }
-        };
-
-        if let Err(err) = response.json::<BasicResponse>().await {
-            println!("json err: {:#?}", err);
-            return Err(err.into());
-        }
+        let params = &params;
+
+        self.with_token(|token| async move {
+            let url = format!("{}{}", self.api_url(CLEAR_QUOTA_PATH), token);
+            let response = self.client.post(url).json(params).send().await?;
+            response::decode::<BasicResponse>(response).await
+        })
+        .await?;
 
         Ok(())
     }
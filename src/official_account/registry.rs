@@ -0,0 +1,219 @@
+use actix_web::{HttpRequest, error, web};
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::OfficialAccount;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{FromRequest, test::TestRequest, web};
+
+    use super::AccountRegistry;
+    use crate::cache::MemoryCache;
+    use crate::official_account::{message::MessageHandler, signature::signature};
+    use crate::test_util::test_config;
+    use crate::{Config, OfficialAccount};
+
+    fn account(appid: &str, token: &str) -> Arc<OfficialAccount> {
+        let config = Config {
+            appid: appid.to_string(),
+            token: token.to_string(),
+            ..test_config()
+        };
+        Arc::new(OfficialAccount::with_cache(
+            config,
+            Arc::new(MemoryCache::new()),
+        ))
+    }
+
+    fn registry() -> AccountRegistry {
+        AccountRegistry::new()
+            .register("gh_aaaaaaaaaaaa", account("wxaaaaaaaaaaaaaaaa", "tokenA"))
+            .register("gh_bbbbbbbbbbbb", account("wxbbbbbbbbbbbbbbbb", "tokenB"))
+    }
+
+    fn body(to_user_name: &str) -> String {
+        format!(
+            "<xml><ToUserName><![CDATA[{}]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1348831860</CreateTime><MsgType><![CDATA[text]]></MsgType><Content><![CDATA[hi]]></Content><MsgId>1</MsgId></xml>",
+            to_user_name
+        )
+    }
+
+    fn query(token: &str) -> String {
+        format!(
+            "timestamp=1744100071&nonce=952645420&signature={}",
+            signature(token, "1744100071", "952645420")
+        )
+    }
+
+    #[actix_web::test]
+    async fn select_by_to_user_name() {
+        let (req, mut payload) = TestRequest::post()
+            .uri(&format!("/wechat?{}", query("tokenB")))
+            .app_data(web::Data::new(registry()))
+            .set_payload(body("gh_bbbbbbbbbbbb"))
+            .to_http_parts();
+
+        let handler = MessageHandler::from_request(&req, &mut payload)
+            .await
+            .unwrap();
+        assert_eq!(handler.account().config.appid, "wxbbbbbbbbbbbbbbbb");
+    }
+
+    #[actix_web::test]
+    async fn select_by_path() {
+        let (req, mut payload) = TestRequest::post()
+            .uri(&format!("/wechat/wxaaaaaaaaaaaaaaaa?{}", query("tokenA")))
+            .param("appid", "wxaaaaaaaaaaaaaaaa")
+            .app_data(web::Data::new(registry()))
+            .set_payload(body("gh_aaaaaaaaaaaa"))
+            .to_http_parts();
+
+        let handler = MessageHandler::from_request(&req, &mut payload)
+            .await
+            .unwrap();
+        assert_eq!(handler.account().config.appid, "wxaaaaaaaaaaaaaaaa");
+    }
+
+    #[actix_web::test]
+    async fn reject_token_of_other_account() {
+        let (req, mut payload) = TestRequest::post()
+            .uri(&format!("/wechat?{}", query("tokenA")))
+            .app_data(web::Data::new(registry()))
+            .set_payload(body("gh_bbbbbbbbbbbb"))
+            .to_http_parts();
+
+        assert!(
+            MessageHandler::from_request(&req, &mut payload)
+                .await
+                .is_err()
+        );
+    }
+
+    #[actix_web::test]
+    async fn reject_unknown_account() {
+        let (req, mut payload) = TestRequest::post()
+            .uri(&format!("/wechat?{}", query("tokenA")))
+            .app_data(web::Data::new(registry()))
+            .set_payload(body("gh_cccccccccccc"))
+            .to_http_parts();
+
+        assert!(
+            MessageHandler::from_request(&req, &mut payload)
+                .await
+                .is_err()
+        );
+    }
+}
+
+/// The path parameter that selects an account, e.g. `/wechat/{appid}`.
+pub const APPID_PATH_PARAM: &str = "appid";
+
+/// Several official accounts served by one callback endpoint.
+///
+/// Register it as actix app data instead of a single `OfficialAccount`. The
+/// account of a callback is selected by the `{appid}` path parameter when the
+/// route has one, and otherwise by the `ToUserName` (original id, `gh_...`) of
+/// the message.
+/// Server URL verifications, which have no message, are matched by the token
+/// that signed them instead; see `signature::verify_server`.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use actix_web::{App, web};
+/// use async_wechat::OfficialAccount;
+/// use async_wechat::official_account::registry::AccountRegistry;
+///
+/// # fn accounts() -> (OfficialAccount, OfficialAccount) { unimplemented!() }
+/// let (shop, news) = accounts();
+/// let registry = AccountRegistry::new()
+///     .register("gh_0123456789ab", Arc::new(shop))
+///     .register("gh_ba9876543210", Arc::new(news));
+///
+/// let app = App::new().app_data(web::Data::new(registry));
+/// ```
+#[derive(Default)]
+pub struct AccountRegistry {
+    by_appid: HashMap<String, Arc<OfficialAccount>>,
+    by_original_id: HashMap<String, Arc<OfficialAccount>>,
+}
+
+impl AccountRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an account under its original id (`gh_...`) and its appid.
+    pub fn register(
+        mut self,
+        original_id: impl Into<String>,
+        account: Arc<OfficialAccount>,
+    ) -> Self {
+        self.by_appid
+            .insert(account.config.appid.clone(), Arc::clone(&account));
+        self.by_original_id.insert(original_id.into(), account);
+        self
+    }
+
+    /// Returns the account with the given appid.
+    pub fn by_appid(&self, appid: &str) -> Option<&Arc<OfficialAccount>> {
+        self.by_appid.get(appid)
+    }
+
+    /// Returns the account with the given original id (`gh_...`).
+    pub fn by_original_id(&self, original_id: &str) -> Option<&Arc<OfficialAccount>> {
+        self.by_original_id.get(original_id)
+    }
+
+    /// Returns every registered account, in no particular order.
+    pub fn accounts(&self) -> impl Iterator<Item = &Arc<OfficialAccount>> {
+        self.by_appid.values()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "xml")]
+struct Recipient {
+    #[serde(rename = "ToUserName")]
+    to_user_name: Option<String>,
+}
+
+/// Finds the account a callback is addressed to.
+///
+/// An `AccountRegistry` registered as app data takes precedence over a single
+/// `OfficialAccount`. `body` is the raw XML of the callback, if any; it is only
+/// read when the account is selected by `ToUserName`.
+pub(crate) fn resolve_account(
+    req: &HttpRequest,
+    body: Option<&str>,
+) -> Result<Arc<OfficialAccount>, actix_web::Error> {
+    if let Some(registry) = req.app_data::<web::Data<AccountRegistry>>() {
+        if let Some(appid) = req.match_info().get(APPID_PATH_PARAM) {
+            return registry
+                .by_appid(appid)
+                .cloned()
+                .ok_or_else(|| error::ErrorNotFound(format!("Unknown appid {}", appid)));
+        }
+
+        let original_id = body
+            .and_then(|xml| from_str::<Recipient>(xml).ok())
+            .and_then(|recipient| recipient.to_user_name)
+            .ok_or_else(|| error::ErrorBadRequest("Missing ToUserName"))?;
+        return registry
+            .by_original_id(&original_id)
+            .cloned()
+            .ok_or_else(|| error::ErrorNotFound(format!("Unknown ToUserName {}", original_id)));
+    }
+
+    if let Some(account) = req.app_data::<web::Data<OfficialAccount>>() {
+        return Ok(account.clone().into_inner());
+    }
+
+    Err(error::ErrorInternalServerError(
+        "Neither OfficialAccount nor AccountRegistry is registered as app data",
+    ))
+}
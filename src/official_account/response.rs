@@ -0,0 +1,71 @@
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{Result, WechatError};
+
+#[cfg(test)]
+mod tests {
+    use crate::WechatError;
+    use crate::official_account::core::BasicResponse;
+    use crate::official_account::qrcode::TicketResponse;
+
+    use super::decode_str;
+
+    #[test]
+    fn decode_api_error() {
+        let body = r#"{"errcode":40013,"errmsg":"invalid appid"}"#;
+        let err = decode_str::<BasicResponse>(body).unwrap_err();
+
+        assert!(matches!(err, WechatError::Api { errcode: 40013, .. }));
+    }
+
+    #[test]
+    fn decode_api_error_before_payload() {
+        let body = r#"{"errcode":40001,"errmsg":"invalid credential"}"#;
+        let err = decode_str::<TicketResponse>(body).unwrap_err();
+
+        assert_eq!(err.errcode(), Some(40001));
+    }
+
+    #[test]
+    fn decode_success() {
+        let body = r#"{"errcode":0,"errmsg":"ok"}"#;
+        let resp = decode_str::<BasicResponse>(body).unwrap();
+        assert_eq!(resp.errcode, 0);
+
+        let body = r#"{"ticket":"t","expire_seconds":60,"url":"http://weixin.qq.com/q/x"}"#;
+        let resp = decode_str::<TicketResponse>(body).unwrap();
+        assert_eq!(resp.ticket, "t");
+    }
+}
+
+/// Decodes a WeChat API response.
+///
+/// Every endpoint goes through this function: a non-success HTTP status or a
+/// non-zero `errcode` in the body is turned into a `WechatError` before the
+/// success payload is decoded into `T`.
+pub(crate) async fn decode<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let response = response.error_for_status()?;
+    let body = response.text().await?;
+
+    decode_str(&body)
+}
+
+pub(crate) fn decode_str<T: DeserializeOwned>(body: &str) -> Result<T> {
+    let value: Value = serde_json::from_str(body)?;
+
+    let errcode = value.get("errcode").and_then(Value::as_i64).unwrap_or(0);
+    if errcode != 0 {
+        let errmsg = value
+            .get("errmsg")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        log::warn!("wechat api error: errcode={}, errmsg={}", errcode, errmsg);
+
+        return Err(WechatError::Api { errcode, errmsg });
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
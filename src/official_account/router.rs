@@ -0,0 +1,765 @@
+use actix_web::HttpResponse;
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::custom::CustomMessage;
+use super::inbound::InboundMessage;
+use super::message::{EventType, MessageHandler, WeChatResponse};
+use crate::OfficialAccount;
+use crate::Result;
+use crate::constants::keys;
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+    use regex::Regex;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{DedupMiddleware, MessageRouter, RateLimitMiddleware, Reply};
+    use crate::OfficialAccount;
+    use crate::cache::{Cache, MemoryCache};
+    use crate::official_account::inbound::InboundMessage;
+    use crate::official_account::message::{EventType, MessageHandler, MsgType};
+    use crate::test_util::{MockServer, inbound_xml, test_config};
+
+    fn text(content: &str) -> MessageHandler {
+        message(&format!(
+            "<MsgType><![CDATA[text]]></MsgType><Content><![CDATA[{}]]></Content><MsgId>1</MsgId>",
+            content
+        ))
+    }
+
+    fn event(event: &str, key: &str) -> MessageHandler {
+        message(&format!(
+            "<MsgType><![CDATA[event]]></MsgType><Event><![CDATA[{}]]></Event><EventKey><![CDATA[{}]]></EventKey>",
+            event, key
+        ))
+    }
+
+    fn message(body: &str) -> MessageHandler {
+        let xml = inbound_xml(body);
+        let account = OfficialAccount::with_cache(test_config(), Arc::new(MemoryCache::new()));
+        MessageHandler::new(
+            InboundMessage::from_xml(&xml).unwrap(),
+            Arc::new(account),
+            None,
+        )
+    }
+
+    fn reply_with(
+        content: &'static str,
+    ) -> impl Fn(Arc<MessageHandler>) -> super::BoxFuture<'static, crate::Result<Reply>> {
+        move |msg| Box::pin(async move { Ok(msg.message.plaintext(content).into()) })
+    }
+
+    fn router() -> MessageRouter {
+        MessageRouter::new()
+            .text("help", reply_with("help"))
+            .regex(Regex::new(r"^order \d+$").unwrap(), reply_with("order"))
+            .event_key("V1001_TODAY_MUSIC", reply_with("music"))
+            .event(EventType::SUBSCRIBE, reply_with("welcome"))
+            .predicate(
+                |message| {
+                    message.from_user_name() == "oUser" && message.msg_type() == MsgType::IMAGE
+                },
+                reply_with("image"),
+            )
+            .msg_type(MsgType::TEXT, reply_with("text"))
+    }
+
+    async fn body(router: &MessageRouter, message: MessageHandler) -> String {
+        let resp = router.handle(message).await;
+        let bytes = to_bytes(resp.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn route_messages() {
+        let router = router();
+
+        assert!(
+            body(&router, text("help"))
+                .await
+                .contains("<![CDATA[help]]>")
+        );
+        assert!(
+            body(&router, text("order 42"))
+                .await
+                .contains("<![CDATA[order]]>")
+        );
+        assert!(
+            body(&router, text("hello"))
+                .await
+                .contains("<![CDATA[text]]>")
+        );
+        assert!(
+            body(&router, event("CLICK", "V1001_TODAY_MUSIC"))
+                .await
+                .contains("<![CDATA[music]]>")
+        );
+        assert!(
+            body(&router, event("subscribe", ""))
+                .await
+                .contains("<![CDATA[welcome]]>")
+        );
+    }
+
+    #[actix_web::test]
+    async fn unmatched_messages() {
+        assert_eq!(body(&router(), event("unsubscribe", "")).await, "success");
+
+        let router = router().fallback(reply_with("fallback"));
+        assert!(
+            body(&router, event("unsubscribe", ""))
+                .await
+                .contains("<![CDATA[fallback]]>")
+        );
+    }
+
+    #[actix_web::test]
+    async fn handler_error() {
+        let router = MessageRouter::new()
+            .fallback(|_| async { Err(crate::WechatError::InvalidArgument("boom".to_string())) });
+        assert_eq!(body(&router, text("hello")).await, "success");
+    }
+
+    #[actix_web::test]
+    async fn dedup() {
+        let router = router().middleware(DedupMiddleware::new());
+        let account = Arc::new(OfficialAccount::with_cache(
+            test_config(),
+            Arc::new(MemoryCache::new()),
+        ));
+        let with_account = |message: MessageHandler| {
+            MessageHandler::new(message.message, Arc::clone(&account), None)
+        };
+
+        assert_ne!(body(&router, with_account(text("hello"))).await, "success");
+        assert_eq!(body(&router, with_account(text("hello"))).await, "success");
+
+        let welcome = body(&router, with_account(event("subscribe", ""))).await;
+        assert!(welcome.contains("<![CDATA[welcome]]>"));
+        assert_eq!(
+            body(&router, with_account(event("subscribe", ""))).await,
+            "success"
+        );
+    }
+
+    #[actix_web::test]
+    async fn dedup_keys_expire() {
+        let router = router().middleware(DedupMiddleware::new().with_ttl(Duration::ZERO));
+        let cache = Arc::new(MemoryCache::new());
+        let account = Arc::new(OfficialAccount::with_cache(test_config(), cache.clone()));
+        let with_account = |message: MessageHandler| {
+            MessageHandler::new(message.message, Arc::clone(&account), None)
+        };
+
+        assert_ne!(body(&router, with_account(text("hello"))).await, "success");
+        assert_ne!(body(&router, with_account(text("hello"))).await, "success");
+
+        for i in 0..10_000 {
+            cache
+                .set(&format!("other:{}", i), "1", Duration::ZERO)
+                .await
+                .unwrap();
+        }
+        body(&router, with_account(event("subscribe", ""))).await;
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn dedup_key() {
+        assert_eq!(DedupMiddleware::id(&text("hello").message), "1");
+        assert_eq!(
+            DedupMiddleware::id(&event("subscribe", "").message),
+            "oUser:1348831860"
+        );
+    }
+
+    #[actix_web::test]
+    async fn async_reply() {
+        let server = MockServer::with_token(|_| r#"{"errcode":0,"errmsg":"ok"}"#.to_string()).await;
+        let account = Arc::new(server.account());
+
+        let router = MessageRouter::new()
+            .async_reply(Duration::from_millis(50))
+            .text("fast", reply_with("fast"))
+            .fallback(|msg| async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok(msg.message.plaintext("slow").into())
+            });
+        let with_account = |message: MessageHandler| {
+            MessageHandler::new(message.message, Arc::clone(&account), None)
+        };
+
+        let fast = body(&router, with_account(text("fast"))).await;
+        assert!(fast.contains("<![CDATA[fast]]>"));
+
+        assert_eq!(body(&router, with_account(text("slow"))).await, "success");
+        assert!(server.requests().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let requests = server.requests();
+        let send = requests
+            .iter()
+            .find(|req| req.target.starts_with("/cgi-bin/message/custom/send"))
+            .unwrap();
+        assert_eq!(
+            send.body,
+            r#"{"touser":"oUser","msgtype":"text","text":{"content":"slow"}}"#
+        );
+    }
+
+    #[actix_web::test]
+    async fn async_reply_to_push_event() {
+        let server = MockServer::with_token(|_| r#"{"errcode":0,"errmsg":"ok"}"#.to_string()).await;
+        let account = Arc::new(server.account());
+
+        let router = MessageRouter::new()
+            .async_reply(Duration::from_millis(50))
+            .fallback(|msg| async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok(msg.message.plaintext("slow").into())
+            });
+
+        for name in [
+            EventType::TEMPLATE_SEND_JOB_FINISH,
+            EventType::MASS_SEND_JOB_FINISH,
+            EventType::UNSUBSCRIBE,
+        ] {
+            let message = message(&format!(
+                "<MsgType><![CDATA[event]]></MsgType><Event><![CDATA[{}]]></Event><MsgID>1000001625</MsgID><Status><![CDATA[success]]></Status><TotalCount>1</TotalCount><FilterCount>1</FilterCount><SentCount>1</SentCount><ErrorCount>0</ErrorCount>",
+                name
+            ))
+            .message;
+            let handler = MessageHandler::new(message, Arc::clone(&account), None);
+            assert_eq!(body(&router, handler).await, "success");
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(server.requests().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn rate_limit() {
+        let router = router().middleware(RateLimitMiddleware::new(2, Duration::from_secs(60)));
+
+        assert_ne!(body(&router, text("hello")).await, "success");
+        assert_ne!(body(&router, text("hello")).await, "success");
+        assert_eq!(body(&router, text("hello")).await, "success");
+    }
+}
+
+/// Body WeChat expects when there is nothing to reply.
+pub const SUCCESS: &str = "success";
+
+/// Suggested [`MessageRouter::async_reply`] timeout, under WeChat's 5-second
+/// callback window.
+pub const ASYNC_REPLY_TIMEOUT: Duration = Duration::from_millis(4500);
+
+/// What a handler answers to a message.
+#[derive(Debug)]
+pub enum Reply {
+    /// A passive reply, encrypted if the request was.
+    Message(Box<WeChatResponse>),
+    /// No reply: answers `success`, so WeChat neither retries nor tells the
+    /// user the account is unavailable.
+    Success,
+}
+
+impl From<WeChatResponse> for Reply {
+    fn from(reply: WeChatResponse) -> Self {
+        Reply::Message(Box::new(reply))
+    }
+}
+
+/// A boxed future returned by handlers and middleware.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+type Handler = Arc<dyn Fn(Arc<MessageHandler>) -> BoxFuture<'static, Result<Reply>> + Send + Sync>;
+
+type Predicate = Arc<dyn Fn(&InboundMessage) -> bool + Send + Sync>;
+
+/// Runs around every handler, e.g. to log, deduplicate or rate limit
+/// messages. Call `next.run(message)` to continue, or return early to skip
+/// the handler.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    async fn handle(&self, message: Arc<MessageHandler>, next: Next<'_>) -> Result<Reply>;
+}
+
+/// The rest of the middleware chain, ending with the matched handler.
+pub struct Next<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    router: &'a MessageRouter,
+}
+
+impl Next<'_> {
+    pub async fn run(self, message: Arc<MessageHandler>) -> Result<Reply> {
+        match self.middleware.split_first() {
+            Some((middleware, rest)) => {
+                let next = Next {
+                    middleware: rest,
+                    router: self.router,
+                };
+                middleware.handle(message, next).await
+            }
+            None => self.router.dispatch(message).await,
+        }
+    }
+}
+
+enum Matcher {
+    MsgType(String),
+    Event(String),
+    EventKey(String),
+    Text(String),
+    Regex(Regex),
+    Predicate(Predicate),
+}
+
+impl Matcher {
+    fn matches(&self, message: &InboundMessage) -> bool {
+        let text = || match message {
+            InboundMessage::Text(text) => Some(text.content.as_str()),
+            _ => None,
+        };
+        let event = || match message {
+            InboundMessage::Event(event) => Some(&event.event),
+            _ => None,
+        };
+
+        match self {
+            Matcher::MsgType(msg_type) => message.msg_type() == msg_type,
+            Matcher::Event(name) => event().is_some_and(|event| event.name() == name),
+            Matcher::EventKey(key) => {
+                event().is_some_and(|event| event.event_key() == Some(key.as_str()))
+            }
+            Matcher::Text(content) => text() == Some(content.as_str()),
+            Matcher::Regex(regex) => text().is_some_and(|text| regex.is_match(text)),
+            Matcher::Predicate(predicate) => predicate(message),
+        }
+    }
+}
+
+/// Dispatches inbound messages and events to async handlers.
+///
+/// Routes are tried in the order they were registered; the first match
+/// handles the message. Messages no route matches go to the fallback, or are
+/// answered with `success`.
+///
+/// ```no_run
+/// use actix_web::{App, HttpResponse, web};
+/// use async_wechat::official_account::message::{EventType, MessageHandler, MsgType};
+/// use async_wechat::official_account::router::{LoggingMiddleware, MessageRouter, Reply};
+///
+/// let router = MessageRouter::new()
+///     .middleware(LoggingMiddleware)
+///     .event(EventType::SUBSCRIBE, |msg| async move {
+///         Ok(msg.message.plaintext("Welcome!").into())
+///     })
+///     .msg_type(MsgType::TEXT, |msg| async move {
+///         Ok(msg.message.plaintext("Got it").into())
+///     })
+///     .fallback(|_| async { Ok(Reply::Success) });
+///
+/// async fn callback(router: web::Data<MessageRouter>, msg: MessageHandler) -> HttpResponse {
+///     router.handle(msg).await
+/// }
+///
+/// let app = App::new()
+///     .app_data(web::Data::new(router))
+///     .route("/wechat", web::post().to(callback));
+/// ```
+#[derive(Default)]
+pub struct MessageRouter {
+    routes: Vec<(Matcher, Handler)>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    fallback: Option<Handler>,
+    async_reply_timeout: Option<Duration>,
+}
+
+impl MessageRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles messages of the given `MsgType`, see [`MsgType`](super::message::MsgType).
+    pub fn msg_type<F, Fut>(self, msg_type: &str, handler: F) -> Self
+    where
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.route(Matcher::MsgType(msg_type.to_string()), handler)
+    }
+
+    /// Handles events of the given `Event`, see [`EventType`](super::message::EventType).
+    pub fn event<F, Fut>(self, event: &str, handler: F) -> Self
+    where
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.route(Matcher::Event(event.to_string()), handler)
+    }
+
+    /// Handles events with the given `EventKey`, e.g. a menu button key.
+    pub fn event_key<F, Fut>(self, key: &str, handler: F) -> Self
+    where
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.route(Matcher::EventKey(key.to_string()), handler)
+    }
+
+    /// Handles text messages whose content is exactly `content`.
+    pub fn text<F, Fut>(self, content: &str, handler: F) -> Self
+    where
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.route(Matcher::Text(content.to_string()), handler)
+    }
+
+    /// Handles text messages whose content matches `regex`.
+    pub fn regex<F, Fut>(self, regex: Regex, handler: F) -> Self
+    where
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.route(Matcher::Regex(regex), handler)
+    }
+
+    /// Handles messages for which `predicate` returns `true`.
+    pub fn predicate<P, F, Fut>(self, predicate: P, handler: F) -> Self
+    where
+        P: Fn(&InboundMessage) -> bool + Send + Sync + 'static,
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.route(Matcher::Predicate(Arc::new(predicate)), handler)
+    }
+
+    /// Handles messages no route matches.
+    pub fn fallback<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.fallback = Some(boxed(handler));
+        self
+    }
+
+    /// Answers `success` when a handler is still running after `timeout`, and
+    /// delivers its reply later through the customer-service send API.
+    ///
+    /// WeChat drops a callback after 5 seconds, so a timeout of about 4.5
+    /// seconds ([`ASYNC_REPLY_TIMEOUT`]) leaves room for the network.
+    /// Customer-service messages only reach users who interacted with the
+    /// account in the last 48 hours. Messages and the subscribe, scan and
+    /// click-like menu events count as such an interaction, but pushes the
+    /// user did not trigger, such as `TEMPLATESENDJOBFINISH`,
+    /// `MASSSENDJOBFINISH` or `unsubscribe`, do not: late replies to those
+    /// are logged and dropped.
+    pub fn async_reply(mut self, timeout: Duration) -> Self {
+        self.async_reply_timeout = Some(timeout);
+        self
+    }
+
+    /// Adds a middleware. Middleware runs in the order it was added, before
+    /// the route is matched.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Runs the middleware and the matching handler, and turns the reply
+    /// into the HTTP response for WeChat.
+    ///
+    /// A handler error is logged and answered with `success`, so that WeChat
+    /// does not retry a message that was partly handled.
+    pub async fn handle(&self, message: MessageHandler) -> HttpResponse {
+        let message = Arc::new(message);
+        let next = Next {
+            middleware: &self.middleware,
+            router: self,
+        };
+
+        let reply = match next.run(Arc::clone(&message)).await {
+            Ok(Reply::Message(reply)) => message.to_string(&reply),
+            Ok(Reply::Success) => return success(),
+            Err(err) => {
+                log::error!(
+                    "wechat message handler failed: msg_type={}, from={}, error={}",
+                    message.message.msg_type(),
+                    message.message.from_user_name(),
+                    err
+                );
+                return success();
+            }
+        };
+
+        match reply {
+            Ok(xml) => HttpResponse::Ok()
+                .content_type("application/xml; charset=utf-8")
+                .body(xml),
+            Err(err) => {
+                log::error!("failed to serialize wechat reply: {}", err);
+                success()
+            }
+        }
+    }
+
+    async fn dispatch(&self, message: Arc<MessageHandler>) -> Result<Reply> {
+        let handler = self
+            .routes
+            .iter()
+            .find(|(matcher, _)| matcher.matches(&message.message))
+            .map(|(_, handler)| handler)
+            .or(self.fallback.as_ref());
+
+        let Some(handler) = handler else {
+            return Ok(Reply::Success);
+        };
+
+        let Some(timeout) = self.async_reply_timeout else {
+            return handler(message).await;
+        };
+
+        let account = Arc::clone(message.account());
+        let deliverable = opens_service_window(&message.message);
+        let mut task = tokio::spawn(handler(message));
+        match tokio::time::timeout(timeout, &mut task).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(err)) => {
+                log::error!("wechat message handler panicked: {}", err);
+                Ok(Reply::Success)
+            }
+            Err(_) => {
+                tokio::spawn(async move {
+                    match task.await {
+                        Ok(Ok(reply)) if deliverable => deliver_late_reply(&account, reply).await,
+                        Ok(Ok(_)) => log::warn!(
+                            "late wechat reply dropped: the message does not allow customer-service messages"
+                        ),
+                        Ok(Err(err)) => log::error!("wechat message handler failed: {}", err),
+                        Err(err) => log::error!("wechat message handler panicked: {}", err),
+                    }
+                });
+                Ok(Reply::Success)
+            }
+        }
+    }
+
+    fn route<F, Fut>(mut self, matcher: Matcher, handler: F) -> Self
+    where
+        F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Reply>> + Send + 'static,
+    {
+        self.routes.push((matcher, boxed(handler)));
+        self
+    }
+}
+
+fn boxed<F, Fut>(handler: F) -> Handler
+where
+    F: Fn(Arc<MessageHandler>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Reply>> + Send + 'static,
+{
+    Arc::new(move |message| Box::pin(handler(message)))
+}
+
+/// Returns `true` if WeChat lets the account send customer-service messages
+/// to the sender of `message`.
+///
+/// [客服消息](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Service_Center_messages.html)
+fn opens_service_window(message: &InboundMessage) -> bool {
+    match message {
+        InboundMessage::Event(event) => matches!(
+            event.event.name(),
+            EventType::SUBSCRIBE
+                | EventType::SCAN
+                | EventType::CLICK
+                | EventType::SCANCODE_PUSH
+                | EventType::SCANCODE_WAITMSG
+        ),
+        _ => true,
+    }
+}
+
+/// Sends the reply of a handler that outlived the callback as a
+/// customer-service message.
+async fn deliver_late_reply(account: &OfficialAccount, reply: Reply) {
+    let Reply::Message(reply) = reply else {
+        return;
+    };
+
+    let Some(message) = CustomMessage::from_reply(&reply) else {
+        log::warn!(
+            "wechat reply of type {} cannot be sent as a customer-service message",
+            reply.msg_type
+        );
+        return;
+    };
+
+    if let Err(err) = account.send_custom_message(&message).await {
+        log::error!(
+            "failed to deliver wechat reply to {}: {}",
+            reply.to_user_name,
+            err
+        );
+    }
+}
+
+fn success() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body(SUCCESS)
+}
+
+/// Logs every message and how long it took to handle.
+pub struct LoggingMiddleware;
+
+#[async_trait]
+impl Middleware for LoggingMiddleware {
+    async fn handle(&self, message: Arc<MessageHandler>, next: Next<'_>) -> Result<Reply> {
+        let started = Instant::now();
+        log::info!(
+            "wechat message: msg_type={}, from={}",
+            message.message.msg_type(),
+            message.message.from_user_name()
+        );
+
+        let reply = next.run(message).await;
+        log::debug!("wechat message handled in {:?}", started.elapsed());
+        reply
+    }
+}
+
+/// Limits how many messages each user (openid) gets handled per window.
+///
+/// Messages over the limit are answered with `success` without running the
+/// handler. Counters live in process memory, so the limit applies per
+/// instance.
+pub struct RateLimitMiddleware {
+    max_messages: u32,
+    window: Duration,
+    counters: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+/// Number of tracked users above which expired counters are dropped.
+const RATE_LIMIT_PRUNE_THRESHOLD: usize = 10_000;
+
+impl RateLimitMiddleware {
+    /// Allows at most `max_messages` per user in every `window`.
+    pub fn new(max_messages: u32, window: Duration) -> Self {
+        RateLimitMiddleware {
+            max_messages,
+            window,
+            counters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn allow(&self, openid: &str) -> bool {
+        let now = Instant::now();
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+
+        if counters.len() > RATE_LIMIT_PRUNE_THRESHOLD {
+            counters.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+
+        let (started, count) = counters.entry(openid.to_string()).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+
+        *count += 1;
+        *count <= self.max_messages
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimitMiddleware {
+    async fn handle(&self, message: Arc<MessageHandler>, next: Next<'_>) -> Result<Reply> {
+        if !self.allow(message.message.from_user_name()) {
+            log::warn!(
+                "wechat message rate limited: from={}",
+                message.message.from_user_name()
+            );
+            return Ok(Reply::Success);
+        }
+
+        next.run(message).await
+    }
+}
+
+/// Answers WeChat's retries of a callback with `success` instead of handling
+/// the same message again.
+///
+/// WeChat retries a callback up to three times when it gets no answer within
+/// 5 seconds. Each message is recorded in the account's cache under its
+/// `MsgId`, or `FromUserName` + `CreateTime` for events, which carry none;
+/// later deliveries within the TTL skip the handler. Cache failures are
+/// logged and the message is handled.
+///
+/// Keys are written with the TTL and left to expire. [`MemoryCache`] sweeps
+/// expired keys once it holds more than 10 000 entries, so its memory stays
+/// bounded by the messages received within one TTL.
+///
+/// [`MemoryCache`]: crate::cache::MemoryCache
+pub struct DedupMiddleware {
+    ttl: Duration,
+}
+
+/// Long enough to cover the three retries, 5 seconds apart.
+const DEFAULT_DEDUP_TTL: Duration = Duration::from_secs(60);
+
+impl DedupMiddleware {
+    pub fn new() -> Self {
+        DedupMiddleware {
+            ttl: DEFAULT_DEDUP_TTL,
+        }
+    }
+
+    /// Sets how long a message is remembered (60 seconds by default).
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn id(message: &InboundMessage) -> String {
+        match message.msg_id() {
+            Some(msg_id) => msg_id.to_string(),
+            None => format!("{}:{}", message.from_user_name(), message.create_time()),
+        }
+    }
+}
+
+impl Default for DedupMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for DedupMiddleware {
+    async fn handle(&self, message: Arc<MessageHandler>, next: Next<'_>) -> Result<Reply> {
+        let account = message.account();
+        let key = account.cache_key(&format!("{}:{}", keys::DEDUP, Self::id(&message.message)));
+
+        match account.cache.set_nx(&key, "1", self.ttl).await {
+            Ok(true) => {}
+            Ok(false) => {
+                log::info!("duplicate wechat message skipped: {}", key);
+                return Ok(Reply::Success);
+            }
+            Err(err) => log::warn!("wechat message dedup failed: {}", err),
+        }
+
+        next.run(message).await
+    }
+}
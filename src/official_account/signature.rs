@@ -1,11 +1,163 @@
+use actix_web::{HttpRequest, HttpResponse, error, web};
 use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 
-use crate::OfficialAccount;
+use crate::{OfficialAccount, Result, WechatError};
+
+use super::registry::{APPID_PATH_PARAM, AccountRegistry, resolve_account};
 
 #[cfg(test)]
 mod tests {
-    use crate::official_account::signature;
+    use std::sync::Arc;
+
+    use actix_web::{App, http::StatusCode, test as actix_test, web};
+
+    use super::{WechatQuery, msg_signature, verify_server};
+    use crate::cache::MemoryCache;
+    use crate::official_account::{registry::AccountRegistry, signature};
+    use crate::test_util::test_config;
+    use crate::{Config, OfficialAccount, WechatError};
+
+    const AES_KEY: &str = "abcdefghijklmnopqrstuvwxyz0123456789ABCDEFG";
+
+    fn account(encoding_aes_key: Option<&str>) -> OfficialAccount {
+        let mut config = test_config();
+        config.encoding_aes_key = encoding_aes_key.map(str::to_string);
+        OfficialAccount::with_cache(config, Arc::new(MemoryCache::new()))
+    }
+
+    fn query(echostr: &str) -> WechatQuery {
+        WechatQuery {
+            timestamp: "1744100071".to_string(),
+            nonce: "952645420".to_string(),
+            signature: signature::signature("wechat", "1744100071", "952645420"),
+            echostr: Some(echostr.to_string()),
+            encrypt_type: None,
+            msg_signature: None,
+        }
+    }
+
+    #[test]
+    fn verify_echostr() {
+        let echostr = account(None).verify_echostr(&query("5837397520")).unwrap();
+        assert_eq!(echostr, "5837397520");
+    }
+
+    #[test]
+    fn verify_echostr_bad_signature() {
+        let mut query = query("5837397520");
+        query.signature = "bad".to_string();
+
+        let err = account(None).verify_echostr(&query).unwrap_err();
+        assert!(matches!(err, WechatError::InvalidSignature));
+    }
+
+    #[test]
+    fn verify_echostr_missing() {
+        let mut query = query("5837397520");
+        query.echostr = None;
+
+        let err = account(None).verify_echostr(&query).unwrap_err();
+        assert!(matches!(err, WechatError::MissingParameter("echostr")));
+    }
+
+    #[test]
+    fn verify_encrypted_echostr() {
+        let account = account(Some(AES_KEY));
+        let encrypted = account
+            .message_crypto()
+            .unwrap()
+            .encrypt("5837397520")
+            .unwrap();
+
+        let mut query = query(&encrypted);
+        query.encrypt_type = Some("aes".to_string());
+        query.msg_signature = Some(msg_signature(
+            "wechat",
+            &query.timestamp,
+            &query.nonce,
+            &encrypted,
+        ));
+        assert_eq!(account.verify_echostr(&query).unwrap(), "5837397520");
+
+        query.msg_signature = Some("bad".to_string());
+        assert!(account.verify_echostr(&query).is_err());
+    }
+
+    #[actix_web::test]
+    async fn verify_server_handler() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(account(None)))
+                .route("/wechat", web::get().to(verify_server)),
+        )
+        .await;
+
+        let uri = format!(
+            "/wechat?timestamp=1744100071&nonce=952645420&signature={}&echostr=5837397520",
+            signature::signature("wechat", "1744100071", "952645420")
+        );
+        let resp =
+            actix_test::call_service(&app, actix_test::TestRequest::get().uri(&uri).to_request())
+                .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(actix_test::read_body(resp).await, "5837397520");
+
+        let uri = "/wechat?timestamp=1744100071&nonce=952645420&signature=bad&echostr=5837397520";
+        let resp =
+            actix_test::call_service(&app, actix_test::TestRequest::get().uri(uri).to_request())
+                .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn verify_server_with_registry() {
+        let account = |appid: &str, token: &str| {
+            let config = Config {
+                appid: appid.to_string(),
+                token: token.to_string(),
+                ..test_config()
+            };
+            Arc::new(OfficialAccount::with_cache(
+                config,
+                Arc::new(MemoryCache::new()),
+            ))
+        };
+        let registry = AccountRegistry::new()
+            .register("gh_aaaaaaaaaaaa", account("wxaaaaaaaaaaaaaaaa", "tokenA"))
+            .register("gh_bbbbbbbbbbbb", account("wxbbbbbbbbbbbbbbbb", "tokenB"));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(registry))
+                .route("/wechat", web::get().to(verify_server))
+                .route("/wechat/{appid}", web::get().to(verify_server)),
+        )
+        .await;
+
+        let call = |path: &str, token: &str| {
+            let uri = format!(
+                "{}?timestamp=1744100071&nonce=952645420&signature={}&echostr=5837397520",
+                path,
+                signature::signature(token, "1744100071", "952645420")
+            );
+            actix_test::TestRequest::get().uri(&uri).to_request()
+        };
+
+        let resp = actix_test::call_service(&app, call("/wechat", "tokenB")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(actix_test::read_body(resp).await, "5837397520");
+
+        let resp = actix_test::call_service(&app, call("/wechat", "tokenC")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp =
+            actix_test::call_service(&app, call("/wechat/wxaaaaaaaaaaaaaaaa", "tokenA")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp =
+            actix_test::call_service(&app, call("/wechat/wxaaaaaaaaaaaaaaaa", "tokenB")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
 
     #[tokio::test]
     async fn request_validate() {
@@ -16,12 +168,22 @@ mod tests {
     }
 }
 
+/// Query parameters WeChat appends to every callback request.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WechatQuery {
-    timestamp: String,
-    nonce: String,
-    signature: String,
-    echostr: Option<String>, // 验证时才需要
+    pub timestamp: String,
+    pub nonce: String,
+    pub signature: String,
+    pub echostr: Option<String>,       // 验证时才需要
+    pub encrypt_type: Option<String>,  // 安全模式/兼容模式时为 aes
+    pub msg_signature: Option<String>, // 安全模式/兼容模式时的消息签名
+}
+
+impl WechatQuery {
+    /// Returns `true` if the request uses "安全模式" or "兼容模式".
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypt_type.as_deref() == Some("aes")
+    }
 }
 
 impl OfficialAccount {
@@ -39,11 +201,113 @@ impl OfficialAccount {
         let sha1 = signature(&self.config.token, &query.timestamp, &query.nonce);
         sha1 == query.signature
     }
+
+    /// Answers the server URL verification sent when the callback URL is
+    /// saved in the WeChat console.
+    ///
+    /// Checks the signature and returns the `echostr` WeChat expects back,
+    /// decrypting it first when the request is encrypted.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidSignature` if `signature` or
+    ///   `msg_signature` does not match.
+    /// * Returns `WechatError::MissingParameter` if `echostr`, or
+    ///   `msg_signature` in safe mode, is missing.
+    /// * Returns `WechatError::Crypto` if the `echostr` cannot be decrypted.
+    pub fn verify_echostr(&self, query: &WechatQuery) -> Result<String> {
+        if signature(&self.config.token, &query.timestamp, &query.nonce) != query.signature {
+            return Err(WechatError::InvalidSignature);
+        }
+
+        let echostr = query
+            .echostr
+            .as_deref()
+            .ok_or(WechatError::MissingParameter("echostr"))?;
+        if !query.is_encrypted() {
+            return Ok(echostr.to_string());
+        }
+
+        let msg_signature = query
+            .msg_signature
+            .as_deref()
+            .ok_or(WechatError::MissingParameter("msg_signature"))?;
+        let crypto = self.message_crypto()?;
+        crypto.verify(msg_signature, &query.timestamp, &query.nonce, echostr)?;
+        crypto.decrypt(echostr)
+    }
+}
+
+impl AccountRegistry {
+    /// Answers the server URL verification for whichever registered account
+    /// signed the request, like [`OfficialAccount::verify_echostr`].
+    ///
+    /// The verification request has no body, hence no `ToUserName` to select
+    /// the account by, so the token of every account is tried in turn.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidSignature` if no account's token matches.
+    /// * Otherwise, returns the errors of [`OfficialAccount::verify_echostr`]
+    ///   for the matching account.
+    pub fn verify_echostr(&self, query: &WechatQuery) -> Result<String> {
+        for account in self.accounts() {
+            match account.verify_echostr(query) {
+                Err(WechatError::InvalidSignature) => continue,
+                result => return result,
+            }
+        }
+
+        Err(WechatError::InvalidSignature)
+    }
+}
+
+/// Actix handler for the `GET` server URL verification.
+///
+/// Route it on the same path as the `MessageHandler` endpoint. The account is
+/// a single `OfficialAccount` registered as app data, or comes from an
+/// `AccountRegistry`: by the `{appid}` path parameter when the route has one,
+/// and otherwise by trying the token of every registered account, since the
+/// verification request carries no `ToUserName`.
+///
+/// ```no_run
+/// use actix_web::{App, web};
+/// use async_wechat::official_account::signature::verify_server;
+///
+/// let app = App::new().route("/wechat", web::get().to(verify_server));
+/// ```
+pub async fn verify_server(
+    req: HttpRequest,
+    query: web::Query<WechatQuery>,
+) -> std::result::Result<HttpResponse, actix_web::Error> {
+    let registry = req.app_data::<web::Data<AccountRegistry>>();
+    let result = match registry {
+        Some(registry) if req.match_info().get(APPID_PATH_PARAM).is_none() => {
+            registry.verify_echostr(&query)
+        }
+        _ => resolve_account(&req, None)?.verify_echostr(&query),
+    };
+
+    match result {
+        Ok(echostr) => Ok(HttpResponse::Ok().content_type("text/plain").body(echostr)),
+        Err(err @ WechatError::InvalidSignature) => Err(error::ErrorUnauthorized(err)),
+        Err(err @ WechatError::Config(_)) => Err(error::ErrorInternalServerError(err)),
+        Err(err) => Err(error::ErrorBadRequest(err)),
+    }
 }
 
 /// [消息解密](https://developers.weixin.qq.com/doc/offiaccount/Message_Management/Message_encryption_and_decryption_instructions.html)
 pub fn signature(token: &str, timestamp: &str, nonce: &str) -> String {
-    let mut params = vec![token.to_string(), timestamp.to_string(), nonce.to_string()];
+    sorted_sha1([token, timestamp, nonce])
+}
+
+/// Computes the `msg_signature` of an encrypted message, which also covers
+/// the `Encrypt` field.
+pub fn msg_signature(token: &str, timestamp: &str, nonce: &str, encrypt: &str) -> String {
+    sorted_sha1([token, timestamp, nonce, encrypt])
+}
+
+fn sorted_sha1<const N: usize>(mut params: [&str; N]) -> String {
     params.sort();
 
     let combined = params.join("");
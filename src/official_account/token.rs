@@ -1,11 +1,139 @@
-use deadpool_redis::redis::cmd;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
 use url::{Url, form_urlencoded};
 
-use crate::OfficialAccount;
 use crate::constants::keys;
+use crate::{OfficialAccount, Result, WechatError};
 
-pub(crate) const TOKEN_URL: &str = "https://api.weixin.qq.com/cgi-bin/token";
+use super::response;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::TokenPolicy;
+    use crate::cache::MemoryCache;
+    use crate::constants::keys;
+    use crate::test_util::MockServer;
+    use crate::{Config, OfficialAccount};
+
+    #[tokio::test]
+    async fn token_is_fetched_once() {
+        let server =
+            MockServer::start(|_| r#"{"access_token":"t1","expires_in":7200}"#.into()).await;
+        let account = Arc::new(server.account());
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let account = Arc::clone(&account);
+                tokio::spawn(async move { account.token().await.unwrap() })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), "t1");
+        }
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(
+            requests[0]
+                .target
+                .starts_with("/cgi-bin/token?appid=wx1234567890abcdef")
+        );
+    }
+
+    #[tokio::test]
+    async fn token_error_is_returned() {
+        let server =
+            MockServer::start(|_| r#"{"errcode":40013,"errmsg":"invalid appid"}"#.into()).await;
+        let account = server.account();
+
+        let err = account.token().await.unwrap_err();
+        assert_eq!(err.errcode(), Some(40013));
+    }
+
+    #[tokio::test]
+    async fn retry_with_fresh_token() {
+        let issued = AtomicUsize::new(0);
+        let server = MockServer::start(move |req| {
+            if req.target.starts_with("/cgi-bin/token") {
+                let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                format!(r#"{{"access_token":"t{}","expires_in":7200}}"#, n)
+            } else if req.target.ends_with("access_token=t1") {
+                r#"{"errcode":40001,"errmsg":"invalid credential"}"#.into()
+            } else {
+                r#"{"errcode":0,"errmsg":"ok"}"#.into()
+            }
+        })
+        .await;
+        let account = server.account();
+
+        account.clear_quota().await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(requests[1].body, r#"{"appid":"wx1234567890abcdef"}"#);
+
+        let targets: Vec<_> = requests.into_iter().map(|r| r.target).collect();
+        assert_eq!(targets.len(), 4);
+        assert!(targets[1].ends_with("access_token=t1"));
+        assert!(targets[3].ends_with("access_token=t2"));
+        assert_eq!(account.token().await.unwrap(), "t2");
+    }
+
+    #[test]
+    fn cache_keys_are_namespaced() {
+        let config = Config {
+            appid: "wx1234567890abcdef".to_string(),
+            app_secret: "secret".to_string(),
+            token: "wechat".to_string(),
+            encoding_aes_key: None,
+        };
+        let account = OfficialAccount::with_cache(config, Arc::new(MemoryCache::new()));
+        assert_eq!(
+            account.cache_key(keys::TOKEN),
+            "async-wechat:wx1234567890abcdef:token"
+        );
+
+        let account = account.with_namespace("prod");
+        assert_eq!(
+            account.cache_key(keys::TOKEN_LOCK),
+            "prod:wx1234567890abcdef:token:lock"
+        );
+    }
+
+    #[test]
+    fn ttl_from_expires_in() {
+        let policy = TokenPolicy::default();
+        assert_eq!(policy.ttl(7200), Duration::from_secs(6900));
+
+        let policy = TokenPolicy {
+            safety_margin: Duration::from_secs(600),
+            ..Default::default()
+        };
+        assert_eq!(policy.ttl(7200), Duration::from_secs(6600));
+        assert_eq!(policy.ttl(300), Duration::from_secs(1));
+    }
+}
+
+pub(crate) const TOKEN_PATH: &str = "/cgi-bin/token";
+
+/// How long the refresh lock is held at most, in case its owner dies.
+const TOKEN_LOCK_TTL: Duration = Duration::from_secs(10);
+/// How long a worker waits for another one to publish a refreshed token.
+const TOKEN_LOCK_WAIT: Duration = Duration::from_secs(15);
+const TOKEN_LOCK_POLL: Duration = Duration::from_millis(100);
+
+const REFRESHER_MIN_INTERVAL: Duration = Duration::from_secs(1);
+const REFRESHER_MAX_INTERVAL: Duration = Duration::from_secs(600);
+const REFRESHER_RETRY_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TokenResponse {
@@ -13,36 +141,132 @@ pub struct TokenResponse {
     expires_in: u64,
 }
 
+/// Controls how long access tokens are cached and when they are refreshed.
+#[derive(Debug, Clone)]
+pub struct TokenPolicy {
+    /// Subtracted from the `expires_in` reported by WeChat when caching a token,
+    /// so that a cached token is never used right at its expiry.
+    pub safety_margin: Duration,
+    /// The background refresher renews the global token once its remaining
+    /// cache lifetime drops below this value.
+    pub refresh_threshold: Duration,
+}
+
+impl Default for TokenPolicy {
+    fn default() -> Self {
+        TokenPolicy {
+            safety_margin: Duration::from_secs(300),
+            refresh_threshold: Duration::from_secs(600),
+        }
+    }
+}
+
+impl TokenPolicy {
+    /// Returns the cache lifetime for a token valid for `expires_in` seconds.
+    pub fn ttl(&self, expires_in: u64) -> Duration {
+        Duration::from_secs(
+            expires_in
+                .saturating_sub(self.safety_margin.as_secs())
+                .max(1),
+        )
+    }
+}
+
 impl OfficialAccount {
     /// [Retrieves the access token for the official account](https://developers.weixin.qq.com/doc/offiaccount/Basic_Information/Get_access_token.html)
     ///
-    /// This function attempts to fetch the access token from the Redis database.
+    /// This function attempts to fetch the access token from the cache.
     /// If not found, it requests a new token from the WeChat API using the
-    /// application ID and secret, then stores the token in Redis for future use.
+    /// application ID and secret, then stores the token in the cache for future use.
+    ///
+    /// Refreshes are single-flight: within the process a mutex serializes
+    /// callers, and across processes a cache lock (`SET NX PX` on Redis) lets
+    /// exactly one worker call WeChat while the others wait for the new token
+    /// to appear.
     ///
     /// # Returns
     ///
-    /// * A `Result` containing the access token as a `String` on success, or a boxed
-    ///   error on failure.
+    /// * A `Result` containing the access token as a `String` on success, or a
+    ///   `WechatError` on failure.
     ///
     /// # Errors
     ///
-    /// * Returns an error if the Redis operation fails, if the HTTP request fails,
-    ///   or if the response cannot be deserialized into a `TokenResponse`.
-    pub async fn token(&self) -> Result<String, Box<dyn std::error::Error>> {
-        let mut rdb = self.rdb_pool.get().await?;
-
-        let value: Option<String> = cmd("GET")
-            .arg(keys::GLOBAL_TOKEN)
-            .query_async(&mut rdb)
+    /// * Returns an error if the cache operation fails, if the HTTP request fails,
+    ///   if WeChat answers with a non-zero `errcode`, if the response cannot be
+    ///   deserialized into a `TokenResponse`, or if another worker holds the
+    ///   refresh lock for longer than the wait timeout.
+    pub async fn token(&self) -> Result<String> {
+        if let Some(token) = self.cached_token().await? {
+            return Ok(token);
+        }
+
+        let _guard = self.refresh_lock.lock().await;
+
+        // another task may have refreshed the token while we were waiting
+        if let Some(token) = self.cached_token().await? {
+            return Ok(token);
+        }
+
+        self.refresh_token().await
+    }
+
+    async fn cached_token(&self) -> Result<Option<String>> {
+        match self.cache.get(&self.cache_key(keys::TOKEN)).await? {
+            Some(bytes) => {
+                let at: TokenResponse = serde_json::from_str(&bytes)?;
+                Ok(Some(at.access_token))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Fetches a new token while holding the cluster-wide refresh lock, or waits
+    /// for the worker holding it to publish the new token.
+    async fn refresh_token(&self) -> Result<String> {
+        let lock_value = lock_value();
+        let deadline = Instant::now() + TOKEN_LOCK_WAIT;
+
+        loop {
+            if self.try_lock(&lock_value).await? {
+                let result = self.fetch_token().await;
+                self.unlock(&lock_value).await;
+
+                return result.map(|at| at.access_token);
+            }
+
+            tokio::time::sleep(TOKEN_LOCK_POLL).await;
+            if let Some(token) = self.cached_token().await? {
+                return Ok(token);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(WechatError::TokenRefreshTimeout);
+            }
+        }
+    }
+
+    async fn try_lock(&self, lock_value: &str) -> Result<bool> {
+        self.cache
+            .set_nx(
+                &self.cache_key(keys::TOKEN_LOCK),
+                lock_value,
+                TOKEN_LOCK_TTL,
+            )
+            .await
+    }
+
+    async fn unlock(&self, lock_value: &str) {
+        if let Err(err) = self
+            .cache
+            .delete_if_eq(&self.cache_key(keys::TOKEN_LOCK), lock_value)
             .await
-            .unwrap_or(None);
-        if let Some(bytes) = value {
-            let at: TokenResponse = serde_json::from_str(&bytes).unwrap();
-            return Ok(at.access_token);
+        {
+            log::warn!("failed to release access token lock: {}", err);
         }
+    }
 
-        let mut url = Url::parse(TOKEN_URL).unwrap();
+    async fn fetch_token(&self) -> Result<TokenResponse> {
+        let mut url = Url::parse(&self.api_url(TOKEN_PATH))?;
         let query = form_urlencoded::Serializer::new(String::new())
             .append_pair("appid", &self.config.appid)
             .append_pair("secret", &self.config.app_secret)
@@ -53,15 +277,142 @@ impl OfficialAccount {
         let url = url.to_string();
 
         let response = self.client.get(&url).send().await?;
-        let at: TokenResponse = response.json::<TokenResponse>().await?;
-        cmd("SETEX")
-            .arg(keys::GLOBAL_TOKEN)
-            .arg(60 * 50 * 2)
-            .arg(serde_json::to_string(&at).unwrap())
-            .query_async::<()>(&mut rdb)
-            .await
-            .unwrap();
+        let at: TokenResponse = response::decode(response).await?;
 
-        Ok(at.access_token)
+        self.cache
+            .set(
+                &self.cache_key(keys::TOKEN),
+                &serde_json::to_string(&at)?,
+                self.token_policy.ttl(at.expires_in),
+            )
+            .await?;
+
+        Ok(at)
+    }
+
+    /// Returns the remaining lifetime of the cached global token, or zero if
+    /// nothing is cached.
+    async fn cached_token_ttl(&self) -> Result<Duration> {
+        let ttl = self.cache.ttl(&self.cache_key(keys::TOKEN)).await?;
+
+        Ok(ttl.unwrap_or_default())
     }
+
+    /// Refreshes the global token if its remaining lifetime is below the
+    /// refresh threshold, and returns the (new) remaining lifetime.
+    ///
+    /// Unlike `token`, this never waits: if another worker holds the refresh
+    /// lock, it is left to publish the new token.
+    async fn refresh_if_expiring(&self) -> Result<Duration> {
+        let threshold = self.token_policy.refresh_threshold;
+
+        let remaining = self.cached_token_ttl().await?;
+        if remaining > threshold {
+            return Ok(remaining);
+        }
+
+        let _guard = self.refresh_lock.lock().await;
+        let lock_value = lock_value();
+        if !self.try_lock(&lock_value).await? {
+            return Ok(remaining);
+        }
+
+        // the token may have been refreshed before we got the lock
+        let result = match self.cached_token_ttl().await {
+            Ok(remaining) if remaining > threshold => Ok(remaining),
+            Ok(_) => self
+                .fetch_token()
+                .await
+                .map(|at| self.token_policy.ttl(at.expires_in)),
+            Err(err) => Err(err),
+        };
+        self.unlock(&lock_value).await;
+
+        result
+    }
+
+    /// Spawns a background task that proactively refreshes the global access
+    /// token whenever its remaining lifetime drops below
+    /// [`TokenPolicy::refresh_threshold`], so that callers of `token` never
+    /// block on a refresh.
+    ///
+    /// The task runs until the returned handle is aborted.
+    pub fn spawn_token_refresher(self: &Arc<Self>) -> JoinHandle<()> {
+        let account = Arc::clone(self);
+
+        tokio::spawn(async move {
+            loop {
+                let delay = match account.refresh_if_expiring().await {
+                    Ok(remaining) => remaining
+                        .saturating_sub(account.token_policy.refresh_threshold)
+                        .clamp(REFRESHER_MIN_INTERVAL, REFRESHER_MAX_INTERVAL),
+                    Err(err) => {
+                        log::warn!("failed to refresh access token: {}", err);
+                        REFRESHER_RETRY_INTERVAL
+                    }
+                };
+
+                tokio::time::sleep(delay).await;
+            }
+        })
+    }
+
+    /// Removes the cached global access token if it is still `stale`.
+    ///
+    /// The comparison keeps a token that another worker has already refreshed
+    /// from being thrown away.
+    pub(crate) async fn invalidate_token(&self, stale: &str) -> Result<()> {
+        let key = self.cache_key(keys::TOKEN);
+        let Some(bytes) = self.cache.get(&key).await? else {
+            return Ok(());
+        };
+
+        let cached = serde_json::from_str::<TokenResponse>(&bytes)
+            .map(|at| at.access_token)
+            .unwrap_or_default();
+        if cached.is_empty() || cached == stale {
+            self.cache.delete_if_eq(&key, &bytes).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs `f` with the global access token.
+    ///
+    /// If WeChat rejects the token (errcode 40001, 40014 or 42001), the cached
+    /// token is invalidated, a fresh one is fetched and `f` is retried once.
+    pub(crate) async fn with_token<T, F, Fut>(&self, f: F) -> Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let token = self.token().await?;
+
+        match f(token.clone()).await {
+            Err(err) if err.is_token_invalid() => {
+                log::warn!("access token rejected ({}), refreshing and retrying", err);
+
+                self.invalidate_token(&token).await?;
+                let token = self.token().await?;
+                f(token).await
+            }
+            result => result,
+        }
+    }
+}
+
+/// Returns a value identifying this lock owner across processes.
+fn lock_value() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{}:{}:{}",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
 }
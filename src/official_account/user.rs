@@ -1,34 +1,405 @@
-use crate::OfficialAccount;
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
 
-use super::core::UserInfoResponse;
+use crate::{OfficialAccount, Result, WechatError};
 
-impl OfficialAccount {
-    pub async fn get_user_by_open_id(
-        &self,
-        open_id: &str,
-    ) -> Result<UserInfoResponse, Box<dyn std::error::Error>> {
-        let token = self.token().await?;
+use super::{core::UserInfoResponse, response};
+
+pub(crate) const USER_INFO_PATH: &str = "/cgi-bin/user/info";
+pub(crate) const USER_GET_PATH: &str = "/cgi-bin/user/get";
+pub(crate) const USER_INFO_BATCHGET_PATH: &str = "/cgi-bin/user/info/batchget?access_token=";
+
+/// Maximum number of openids per `user/info/batchget` request.
+pub const MAX_BATCH_USER_INFO: usize = 100;
+/// Number of `user/info/batchget` requests `batch_get_user_info` keeps in
+/// flight.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_util::TryStreamExt;
+
+    use crate::WechatError;
+    use crate::test_util::MockServer;
+
+    /// Answers `user/info/batchget` with a subscribed user per requested
+    /// openid.
+    fn batchget_response(body: &str) -> String {
+        let request: serde_json::Value = serde_json::from_str(body).unwrap();
+        let users: Vec<_> = request["user_list"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|user| {
+                serde_json::json!({
+                    "subscribe": 1,
+                    "openid": user["openid"],
+                    "language": "zh_CN",
+                    "subscribe_time": 1382694957,
+                    "remark": "",
+                    "groupid": 0,
+                    "tagid_list": [128, 2],
+                    "subscribe_scene": "ADD_SCENE_QR_CODE",
+                    "qr_scene": 98765,
+                    "qr_scene_str": ""
+                })
+            })
+            .collect();
+        serde_json::json!({ "user_info_list": users }).to_string()
+    }
 
-        let url = format!(
-            "https://api.weixin.qq.com/cgi-bin/user/info?access_token={}&openid={}&lang=zh_CN",
-            token, open_id
+    #[tokio::test]
+    async fn followers() {
+        let server = MockServer::with_token(|req| {
+            if req.target.ends_with("next_openid=") {
+                r#"{"total":3,"count":2,"data":{"openid":["OPENID1","OPENID2"]},"next_openid":"OPENID2"}"#
+                    .to_string()
+            } else if req.target.ends_with("next_openid=OPENID2") {
+                r#"{"total":3,"count":1,"data":{"openid":["OPENID3"]},"next_openid":"OPENID3"}"#
+                    .to_string()
+            } else {
+                r#"{"total":3,"count":0,"next_openid":""}"#.to_string()
+            }
+        })
+        .await;
+        let account = server.account();
+
+        let followers: Vec<String> = account.followers().try_collect().await.unwrap();
+        assert_eq!(followers, ["OPENID1", "OPENID2", "OPENID3"]);
+
+        let targets: Vec<String> = server
+            .requests()
+            .into_iter()
+            .filter(|req| req.target.starts_with("/cgi-bin/user/get"))
+            .map(|req| req.target)
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                "/cgi-bin/user/get?access_token=ACCESS_TOKEN&next_openid=",
+                "/cgi-bin/user/get?access_token=ACCESS_TOKEN&next_openid=OPENID2",
+                "/cgi-bin/user/get?access_token=ACCESS_TOKEN&next_openid=OPENID3",
+            ]
         );
-        let response = match self.client.get(url).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                println!("json err: {:#?}", e);
-                return Err(e.into());
+    }
+
+    #[tokio::test]
+    async fn followers_stop_at_error() {
+        let server = MockServer::with_token(|req| {
+            if req.target.ends_with("next_openid=") {
+                r#"{"total":3,"count":1,"data":{"openid":["OPENID1"]},"next_openid":"OPENID1"}"#
+                    .to_string()
+            } else {
+                r#"{"errcode":45009,"errmsg":"reach max api daily quota limit"}"#.to_string()
             }
-        };
+        })
+        .await;
+        let account = server.account();
+
+        let results: Vec<_> = futures_util::StreamExt::collect(account.followers()).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), "OPENID1");
+        assert!(matches!(
+            results[1],
+            Err(WechatError::Api { errcode: 45009, .. })
+        ));
+    }
 
-        let result = match response.json::<UserInfoResponse>().await {
-            Ok(r) => r,
-            Err(e) => {
-                println!("json err: {:#?}", e);
-                return Err(e.into());
+    #[tokio::test]
+    async fn batch_get_user_info() {
+        let batches = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&batches);
+        let server = MockServer::with_token(move |req| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            batchget_response(&req.body)
+        })
+        .await;
+        let account = server.account();
+
+        let openids: Vec<String> = (0..250).map(|i| format!("OPENID{}", i)).collect();
+        let users = account
+            .batch_get_user_info_with_concurrency(&openids, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(batches.load(Ordering::SeqCst), 3);
+        assert_eq!(users.len(), 250);
+        assert!(
+            users
+                .iter()
+                .zip(&openids)
+                .all(|(user, openid)| &user.openid == openid)
+        );
+        assert!(users[0].is_subscribed());
+        assert_eq!(users[0].subscribe_time, Some(1382694957));
+        assert_eq!(users[0].tagid_list, [128, 2]);
+        assert_eq!(
+            users[0].subscribe_scene.as_deref(),
+            Some("ADD_SCENE_QR_CODE")
+        );
+        assert_eq!(users[0].qr_scene, Some(98765));
+
+        let requests = server.requests();
+        let batch = requests
+            .iter()
+            .find(|req| req.target.starts_with("/cgi-bin/user/info/batchget"))
+            .unwrap();
+        assert_eq!(
+            batch.target,
+            "/cgi-bin/user/info/batchget?access_token=ACCESS_TOKEN"
+        );
+        assert!(batch.body.contains(r#"{"openid":"OPENID"#));
+        assert!(batch.body.contains(r#""lang":"zh_CN""#));
+
+        assert!(account.batch_get_user_info(&[]).await.unwrap().is_empty());
+        assert_eq!(batches.load(Ordering::SeqCst), 3);
+        assert!(matches!(
+            account
+                .batch_get_user_info_with_concurrency(&openids, 0)
+                .await,
+            Err(WechatError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn batch_get_user_info_error() {
+        let server = MockServer::with_token(|req| {
+            if req.body.contains("OPENID0") {
+                batchget_response(&req.body)
+            } else {
+                r#"{"errcode":40003,"errmsg":"invalid openid"}"#.to_string()
             }
+        })
+        .await;
+
+        let openids: Vec<String> = (0..150).map(|i| format!("OPENID{}", i)).collect();
+        assert!(matches!(
+            server.account().batch_get_user_info(&openids).await,
+            Err(WechatError::Api { errcode: 40003, .. })
+        ));
+    }
+
+    #[test]
+    fn deserialize_unsubscribed_user() {
+        let body =
+            r#"{"user_info_list":[{"subscribe":0,"openid":"otvxTs_JZ6SEiP0imdhpi50fuSZg"}]}"#;
+        let response =
+            crate::official_account::response::decode_str::<super::BatchGetUserInfoResponse>(body)
+                .unwrap();
+        let user = &response.user_info_list[0];
+        assert!(!user.is_subscribed());
+        assert!(user.tagid_list.is_empty());
+        assert_eq!(user.subscribe_time, None);
+    }
+}
+
+/// A page of the follower list, as returned by `get_user_list`.
+///
+/// [获取用户列表](https://developers.weixin.qq.com/doc/offiaccount/User_Management/Getting_a_User_List.html)
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserListResponse {
+    pub total: u64, // 关注该公众账号的总用户数
+    pub count: u64, // 拉取的OPENID个数，最大值为10000
+    #[serde(default)]
+    pub data: Option<UserListData>, // 没有关注者时不返回
+    #[serde(default)]
+    pub next_openid: String, // 拉取列表的最后一个用户的OPENID
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserListData {
+    pub openid: Vec<String>,
+}
+
+/// The basic information of a user, as returned by `batch_get_user_info`.
+///
+/// [获取用户基本信息](https://developers.weixin.qq.com/doc/offiaccount/User_Management/Get_users_basic_information_UnionID.html)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UserInfo {
+    pub subscribe: u8, // 是否关注，值为0时拉取不到其余信息
+    pub openid: String,
+    pub language: Option<String>,
+    pub subscribe_time: Option<u64>, // 关注时间戳，多次关注取最后关注时间
+    pub unionid: Option<String>,
+    pub remark: Option<String>, // 公众号运营者对粉丝的备注
+    pub groupid: Option<u64>,
+    #[serde(default)]
+    pub tagid_list: Vec<u64>,
+    pub subscribe_scene: Option<String>, // 关注的渠道来源，如 ADD_SCENE_QR_CODE
+    pub qr_scene: Option<u64>,
+    pub qr_scene_str: Option<String>,
+}
+
+impl UserInfo {
+    /// Returns `true` if the user follows the account. Otherwise only
+    /// `openid` is set.
+    pub fn is_subscribed(&self) -> bool {
+        self.subscribe == 1
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BatchGetUserInfoRequest<'a> {
+    user_list: Vec<UserListItem<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct UserListItem<'a> {
+    openid: &'a str,
+    lang: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchGetUserInfoResponse {
+    user_info_list: Vec<UserInfo>,
+}
+
+impl OfficialAccount {
+    pub async fn get_user_by_open_id(&self, open_id: &str) -> Result<UserInfoResponse> {
+        self.with_token(|token| async move {
+            let url = format!(
+                "{}?access_token={}&openid={}&lang=zh_CN",
+                self.api_url(USER_INFO_PATH),
+                token,
+                open_id
+            );
+            let response = self.client.get(url).send().await?;
+            response::decode(response).await
+        })
+        .await
+    }
+
+    /// [获取用户列表](https://developers.weixin.qq.com/doc/offiaccount/User_Management/Getting_a_User_List.html),
+    /// up to 10,000 openids per page.
+    ///
+    /// # Arguments
+    ///
+    /// * `next_openid` - The `next_openid` of the previous page, or `None` for
+    ///   the first page.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode`.
+    pub async fn get_user_list(&self, next_openid: Option<&str>) -> Result<UserListResponse> {
+        self.with_token(|token| async move {
+            let url = format!(
+                "{}?access_token={}&next_openid={}",
+                self.api_url(USER_GET_PATH),
+                token,
+                next_openid.unwrap_or_default()
+            );
+            let response = self.client.get(url).send().await?;
+            response::decode(response).await
+        })
+        .await
+    }
+
+    /// Returns the openids of all followers, fetching the pages of
+    /// `get_user_list` as the stream is polled.
+    ///
+    /// The stream ends after the last page, or after yielding the first
+    /// error.
+    ///
+    /// ```no_run
+    /// use futures_util::TryStreamExt;
+    ///
+    /// # async fn run(account: async_wechat::OfficialAccount) -> async_wechat::Result<()> {
+    /// let mut followers = std::pin::pin!(account.followers());
+    /// while let Some(openid) = followers.try_next().await? {
+    ///     println!("{}", openid);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn followers(&self) -> impl Stream<Item = Result<String>> + Send + '_ {
+        // `None` once the last page has been fetched.
+        let first_page = Some(String::new());
+
+        stream::try_unfold(first_page, move |next_openid| async move {
+            let Some(next_openid) = next_openid else {
+                return Result::Ok(None);
+            };
+
+            let page = self.get_user_list(Some(&next_openid)).await?;
+            let openids = page.data.map(|data| data.openid).unwrap_or_default();
+            let next_openid =
+                (page.count > 0 && !page.next_openid.is_empty()).then_some(page.next_openid);
+
+            Ok(Some((openids, next_openid)))
+        })
+        .map_ok(|openids| stream::iter(openids.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    /// [批量获取用户基本信息](https://developers.weixin.qq.com/doc/offiaccount/User_Management/Get_users_basic_information_UnionID.html)
+    /// for any number of openids, keeping `DEFAULT_BATCH_CONCURRENCY` requests
+    /// in flight; see [`OfficialAccount::batch_get_user_info_with_concurrency`].
+    pub async fn batch_get_user_info(&self, openids: &[String]) -> Result<Vec<UserInfo>> {
+        self.batch_get_user_info_with_concurrency(openids, DEFAULT_BATCH_CONCURRENCY)
+            .await
+    }
+
+    /// [批量获取用户基本信息](https://developers.weixin.qq.com/doc/offiaccount/User_Management/Get_users_basic_information_UnionID.html),
+    /// splitting `openids` into requests of `MAX_BATCH_USER_INFO` and sending
+    /// up to `concurrency` of them at a time.
+    ///
+    /// # Returns
+    ///
+    /// * The information of every user, in the order of `openids`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `WechatError::InvalidArgument` if `concurrency` is 0.
+    /// * Returns an error if an HTTP request fails or returns a non-success status,
+    ///   or if WeChat answers with a non-zero `errcode`. The requests still in
+    ///   flight are then cancelled.
+    pub async fn batch_get_user_info_with_concurrency(
+        &self,
+        openids: &[String],
+        concurrency: usize,
+    ) -> Result<Vec<UserInfo>> {
+        if concurrency == 0 {
+            return Err(WechatError::InvalidArgument(
+                "concurrency must be at least 1".to_string(),
+            ));
+        }
+
+        // Creating the (lazy) futures up front rather than in `map` keeps the
+        // returned future `Send`.
+        let requests: Vec<_> = openids
+            .chunks(MAX_BATCH_USER_INFO)
+            .map(|chunk| self.batch_get_user_info_chunk(chunk))
+            .collect();
+
+        stream::iter(requests)
+            .buffered(concurrency)
+            .try_concat()
+            .await
+    }
+
+    async fn batch_get_user_info_chunk(&self, openids: &[String]) -> Result<Vec<UserInfo>> {
+        let body = &BatchGetUserInfoRequest {
+            user_list: openids
+                .iter()
+                .map(|openid| UserListItem {
+                    openid,
+                    lang: "zh_CN",
+                })
+                .collect(),
         };
 
-        Ok(result)
+        let response = self
+            .with_token(|token| async move {
+                let url = format!("{}{}", self.api_url(USER_INFO_BATCHGET_PATH), token);
+                let response = self.client.post(url).json(body).send().await?;
+                response::decode::<BatchGetUserInfoResponse>(response).await
+            })
+            .await?;
+
+        Ok(response.user_info_list)
     }
 }
@@ -0,0 +1,278 @@
+use std::fmt::Display;
+
+use quick_xml::{
+    SeError, Writer,
+    events::{BytesCData, BytesEnd, BytesStart, BytesText, Event},
+};
+
+use super::message::WeChatResponse;
+use crate::Result;
+
+#[cfg(test)]
+mod tests {
+    use super::write_reply;
+    use crate::official_account::message::{
+        Article, Articles, MediaReply, MusicReply, TransInfo, VideoReply, WeChatResponse,
+    };
+
+    /// Drops the indentation between tags, so that the output can be compared
+    /// with the samples of the WeChat docs regardless of layout.
+    fn normalize(xml: &str) -> String {
+        xml.lines().map(str::trim).collect()
+    }
+
+    fn reply(msg_type: &str) -> WeChatResponse {
+        WeChatResponse {
+            to_user_name: "toUser".to_string(),
+            from_user_name: "fromUser".to_string(),
+            create_time: 12345678,
+            msg_type: msg_type.to_string(),
+            content: None,
+            image: None,
+            voice: None,
+            video: None,
+            music: None,
+            article_count: None,
+            articles: None,
+            trans_info: None,
+        }
+    }
+
+    fn assert_golden(reply: &WeChatResponse, golden: &str) {
+        let xml = write_reply(reply).unwrap();
+        assert_eq!(normalize(&xml), normalize(golden), "\n{}", xml);
+    }
+
+    #[test]
+    fn text() {
+        let reply = WeChatResponse {
+            content: Some("你好".to_string()),
+            ..reply("text")
+        };
+        assert_golden(&reply, include_str!("../../tests/fixtures/reply_text.xml"));
+    }
+
+    #[test]
+    fn image() {
+        let reply = WeChatResponse {
+            image: Some(MediaReply {
+                media_id: "media_id".to_string(),
+            }),
+            ..reply("image")
+        };
+        assert_golden(&reply, include_str!("../../tests/fixtures/reply_image.xml"));
+    }
+
+    #[test]
+    fn voice() {
+        let reply = WeChatResponse {
+            voice: Some(MediaReply {
+                media_id: "media_id".to_string(),
+            }),
+            ..reply("voice")
+        };
+        assert_golden(&reply, include_str!("../../tests/fixtures/reply_voice.xml"));
+    }
+
+    #[test]
+    fn video() {
+        let reply = WeChatResponse {
+            video: Some(VideoReply {
+                media_id: "media_id".to_string(),
+                title: Some("title".to_string()),
+                description: Some("description".to_string()),
+            }),
+            ..reply("video")
+        };
+        assert_golden(&reply, include_str!("../../tests/fixtures/reply_video.xml"));
+    }
+
+    #[test]
+    fn music() {
+        let reply = WeChatResponse {
+            music: Some(MusicReply {
+                title: Some("TITLE".to_string()),
+                description: Some("DESCRIPTION".to_string()),
+                music_url: Some("MUSIC_Url".to_string()),
+                hq_music_url: Some("HQ_MUSIC_Url".to_string()),
+                thumb_media_id: "media_id".to_string(),
+            }),
+            ..reply("music")
+        };
+        assert_golden(&reply, include_str!("../../tests/fixtures/reply_music.xml"));
+    }
+
+    #[test]
+    fn news() {
+        let reply = WeChatResponse {
+            article_count: Some(1),
+            articles: Some(Articles {
+                items: vec![Article {
+                    title: "title1".to_string(),
+                    description: "description1".to_string(),
+                    pic_url: "picurl".to_string(),
+                    url: "url".to_string(),
+                }],
+            }),
+            ..reply("news")
+        };
+        assert_golden(&reply, include_str!("../../tests/fixtures/reply_news.xml"));
+    }
+
+    #[test]
+    fn transfer_customer_service() {
+        let reply = WeChatResponse {
+            trans_info: Some(TransInfo {
+                kf_account: "test1@test".to_string(),
+            }),
+            ..reply("transfer_customer_service")
+        };
+        assert_golden(
+            &reply,
+            include_str!("../../tests/fixtures/reply_transfer_customer_service.xml"),
+        );
+    }
+
+    #[test]
+    fn special_characters() {
+        let reply = WeChatResponse {
+            content: Some("a < b && c > d 😀 ]]> end".to_string()),
+            ..reply("text")
+        };
+        let xml = write_reply(&reply).unwrap();
+        assert!(
+            xml.contains("<Content><![CDATA[a < b && c > d 😀 ]]]]><![CDATA[> end]]></Content>")
+        );
+
+        let parsed = quick_xml::de::from_str::<WeChatResponse>(&xml).unwrap();
+        assert_eq!(parsed.content.as_deref(), Some("a < b && c > d 😀 ]]> end"));
+    }
+}
+
+/// Writes a passive reply in the layout of the WeChat docs, wrapping every
+/// string field in CDATA.
+///
+/// `quick_xml::se` escapes `<` and `&` instead, which some clients render
+/// verbatim.
+pub(crate) fn write_reply(reply: &WeChatResponse) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    write_root(&mut writer, reply)?;
+
+    // Only `&str` is ever written, so the output is valid UTF-8.
+    Ok(String::from_utf8(writer.into_inner()).expect("reply XML is UTF-8"))
+}
+
+fn write_root(
+    writer: &mut Writer<Vec<u8>>,
+    reply: &WeChatResponse,
+) -> std::result::Result<(), SeError> {
+    start(writer, "xml")?;
+    cdata(writer, "ToUserName", &reply.to_user_name)?;
+    cdata(writer, "FromUserName", &reply.from_user_name)?;
+    number(writer, "CreateTime", reply.create_time)?;
+    cdata(writer, "MsgType", &reply.msg_type)?;
+
+    if let Some(content) = &reply.content {
+        cdata(writer, "Content", content)?;
+    }
+
+    if let Some(image) = &reply.image {
+        start(writer, "Image")?;
+        cdata(writer, "MediaId", &image.media_id)?;
+        end(writer, "Image")?;
+    }
+
+    if let Some(voice) = &reply.voice {
+        start(writer, "Voice")?;
+        cdata(writer, "MediaId", &voice.media_id)?;
+        end(writer, "Voice")?;
+    }
+
+    if let Some(video) = &reply.video {
+        start(writer, "Video")?;
+        cdata(writer, "MediaId", &video.media_id)?;
+        optional_cdata(writer, "Title", &video.title)?;
+        optional_cdata(writer, "Description", &video.description)?;
+        end(writer, "Video")?;
+    }
+
+    if let Some(music) = &reply.music {
+        start(writer, "Music")?;
+        optional_cdata(writer, "Title", &music.title)?;
+        optional_cdata(writer, "Description", &music.description)?;
+        optional_cdata(writer, "MusicUrl", &music.music_url)?;
+        optional_cdata(writer, "HQMusicUrl", &music.hq_music_url)?;
+        cdata(writer, "ThumbMediaId", &music.thumb_media_id)?;
+        end(writer, "Music")?;
+    }
+
+    if let Some(article_count) = reply.article_count {
+        number(writer, "ArticleCount", article_count)?;
+    }
+
+    if let Some(articles) = &reply.articles {
+        start(writer, "Articles")?;
+        for article in &articles.items {
+            start(writer, "item")?;
+            cdata(writer, "Title", &article.title)?;
+            cdata(writer, "Description", &article.description)?;
+            cdata(writer, "PicUrl", &article.pic_url)?;
+            cdata(writer, "Url", &article.url)?;
+            end(writer, "item")?;
+        }
+        end(writer, "Articles")?;
+    }
+
+    if let Some(trans_info) = &reply.trans_info {
+        start(writer, "TransInfo")?;
+        cdata(writer, "KfAccount", &trans_info.kf_account)?;
+        end(writer, "TransInfo")?;
+    }
+
+    end(writer, "xml")
+}
+
+fn start(writer: &mut Writer<Vec<u8>>, name: &str) -> std::result::Result<(), SeError> {
+    writer.write_event(Event::Start(BytesStart::new(name)))?;
+    Ok(())
+}
+
+fn end(writer: &mut Writer<Vec<u8>>, name: &str) -> std::result::Result<(), SeError> {
+    writer.write_event(Event::End(BytesEnd::new(name)))?;
+    Ok(())
+}
+
+/// Writes `<name><![CDATA[value]]></name>`, splitting any `]]>` in `value`
+/// across two CDATA sections.
+fn cdata(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    value: &str,
+) -> std::result::Result<(), SeError> {
+    start(writer, name)?;
+    for section in BytesCData::escaped(value) {
+        writer.write_event(Event::CData(section))?;
+    }
+    end(writer, name)
+}
+
+fn optional_cdata(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    value: &Option<String>,
+) -> std::result::Result<(), SeError> {
+    match value {
+        Some(value) => cdata(writer, name, value),
+        None => Ok(()),
+    }
+}
+
+fn number(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    value: impl Display,
+) -> std::result::Result<(), SeError> {
+    start(writer, name)?;
+    writer.write_event(Event::Text(BytesText::new(&value.to_string())))?;
+    end(writer, name)
+}
@@ -0,0 +1,169 @@
+//! Helpers shared by unit tests.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::cache::MemoryCache;
+use crate::official_account::inbound::InboundMessage;
+use crate::{Config, OfficialAccount};
+
+/// The `EncodingAESKey` of the encrypted fixtures.
+pub(crate) const ENCODING_AES_KEY: &str = "abcdefghijklmnopqrstuvwxyz0123456789ABCDEFG";
+
+/// A text message `hello` to [`test_config`]'s appid, encrypted with
+/// [`ENCODING_AES_KEY`].
+// produced with `openssl enc -aes-256-cbc -nopad` from the documented layout
+pub(crate) const ENCRYPTED: &str = "Q3stYC6hdFzMh9T8HCvyDJ4rESBupRSxsJzqQVNO7y8xX05+JTK6WrzwTuW5Tew++GIEl7rAAoPmKfFRkiUcPI/rBXDB/WozInqYyKxAgC55h7Th3LB3Tjd4UdQs0ONKHfdG6G8Fg6+fDzX5Z8SjIj5rlWNk5jmJZRVTc5BwhWJfN0Zua/CeByydcIA5r9IKqodFv+OzZIpDX0PR+XZ9lMXfuHBnBcud4xJ75+Gp2mvmExIVow2z/qxC98+ofacPADVYbsVsJAk+yWeVd5jmdeMTWO5XhO3KjVMWWwBYDcZg4UVrAYFFNTLy6cxAoShX8VmNVU0vsld6CZAXIqxWOl05tYXEDBUIrtLF4kbPBu2+aCVnVE6xaqWD8Ft72ZCc";
+
+/// The body of `/cgi-bin/token` served by [`MockServer::with_token`].
+const TOKEN_RESPONSE: &str = r#"{"access_token":"ACCESS_TOKEN","expires_in":7200}"#;
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub(crate) struct MockRequest {
+    pub method: String,
+    /// The path and query string.
+    pub target: String,
+    pub body: String,
+}
+
+type Responder = dyn Fn(&MockRequest) -> String + Send + Sync;
+
+/// A local stand-in for the WeChat API answering every request with the JSON
+/// body returned by a closure.
+pub(crate) struct MockServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    pub(crate) async fn start(
+        respond: impl Fn(&MockRequest) -> String + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let respond: Arc<Responder> = Arc::new(respond);
+
+        let recorded = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = Arc::clone(&recorded);
+                let respond = Arc::clone(&respond);
+                tokio::spawn(async move {
+                    if let Some(request) = read_request(stream, &*respond).await {
+                        recorded.lock().unwrap().push(request);
+                    }
+                });
+            }
+        });
+
+        MockServer { base_url, requests }
+    }
+
+    /// Starts a server that issues the access token `ACCESS_TOKEN` and
+    /// answers every other request with `respond`.
+    pub(crate) async fn with_token(
+        respond: impl Fn(&MockRequest) -> String + Send + Sync + 'static,
+    ) -> Self {
+        Self::start(move |req| {
+            if req.target.starts_with("/cgi-bin/token") {
+                TOKEN_RESPONSE.to_string()
+            } else {
+                respond(req)
+            }
+        })
+        .await
+    }
+
+    /// Returns the requests received so far.
+    pub(crate) fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns an account with an in-memory cache that talks to this server.
+    pub(crate) fn account(&self) -> OfficialAccount {
+        OfficialAccount::builder(test_config())
+            .cache(Arc::new(MemoryCache::new()))
+            .api_base_url(&self.base_url)
+            .build()
+            .unwrap()
+    }
+}
+
+pub(crate) fn test_config() -> Config {
+    Config {
+        appid: "wx1234567890abcdef".to_string(),
+        app_secret: "secret".to_string(),
+        token: "wechat".to_string(),
+        encoding_aes_key: None,
+    }
+}
+
+/// Wraps `body` in the header of a callback from `oUser` to
+/// `gh_123456789abc`.
+pub(crate) fn inbound_xml(body: &str) -> String {
+    format!(
+        "<xml><ToUserName><![CDATA[gh_123456789abc]]></ToUserName><FromUserName><![CDATA[oUser]]></FromUserName><CreateTime>1348831860</CreateTime>{}</xml>",
+        body
+    )
+}
+
+/// A text message `hi` from `oUser`.
+pub(crate) fn inbound() -> InboundMessage {
+    InboundMessage::from_xml(&inbound_xml(
+        "<MsgType><![CDATA[text]]></MsgType><Content><![CDATA[hi]]></Content><MsgId>1</MsgId>",
+    ))
+    .unwrap()
+}
+
+async fn read_request(mut stream: TcpStream, respond: &Responder) -> Option<MockRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let mut request_line = head.lines().next()?.split_whitespace();
+    let request = MockRequest {
+        method: request_line.next()?.to_string(),
+        target: request_line.next()?.to_string(),
+        body: String::from_utf8_lossy(&buf[header_end..]).to_string(),
+    };
+
+    let body = respond(&request);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await.ok()?;
+
+    Some(request)
+}